@@ -0,0 +1,24 @@
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::*;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use serde::de::IgnoredAny;
+
+fn deserialize(c: &mut Criterion) {
+    let query = hex::decode(GET_PEERS_QUERY).unwrap();
+    let response = hex::decode(GET_PEERS_RESPONSE).unwrap();
+
+    c.bench_function("get_peers query", |b| {
+        b.iter(|| bencode::from_bytes::<Message>(black_box(&query)).unwrap())
+    });
+    c.bench_function("get_peers response", |b| {
+        b.iter(|| bencode::from_bytes::<Message>(black_box(&response)).unwrap())
+    });
+    c.bench_function("get_peers response ignored", |b| {
+        b.iter(|| bencode::from_bytes::<IgnoredAny>(black_box(&response)).unwrap())
+    });
+}
+
+criterion_group!(benches, deserialize);
+criterion_main!(benches);
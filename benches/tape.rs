@@ -0,0 +1,75 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use bencode::tape::Tape;
+use bencode::{Dict, Value};
+
+/// A multi-file torrent with `count` files and 20 bytes of pieces per file.
+fn torrent(count: usize) -> Vec<u8> {
+    let files: Value = (0..count)
+        .map(|index| {
+            Dict::new().insert("length", index as i64).insert(
+                "path",
+                vec![
+                    Value::from("directory"),
+                    Value::from(format!("file{}", index)),
+                ],
+            )
+        })
+        .map(Value::from)
+        .collect();
+    let info = Dict::new()
+        .insert("files", files)
+        .insert("name", "archive")
+        .insert("piece length", 262144)
+        .insert("pieces", vec![0x5a; 20 * count]);
+    let torrent = Dict::new()
+        .insert("announce", "http://tracker.example/announce")
+        .insert("info", info);
+    bencode::to_bytes(&Value::from(torrent)).unwrap()
+}
+
+fn total_length(c: &mut Criterion) {
+    let torrent = torrent(5000);
+
+    c.bench_function("torrent total length tape", |b| {
+        b.iter(|| {
+            let tape = Tape::parse(black_box(&torrent)).unwrap();
+            let files = tape.root().get(b"info").unwrap().get(b"files").unwrap();
+            files
+                .iter()
+                .filter_map(|file| file.get(b"length")?.as_int())
+                .sum::<i64>()
+        })
+    });
+    c.bench_function("torrent total length value", |b| {
+        b.iter(|| {
+            let value: Value = bencode::from_bytes(black_box(&torrent)).unwrap();
+            value
+                .select("/info/files/*/length")
+                .into_iter()
+                .filter_map(Value::as_int)
+                .sum::<i64>()
+        })
+    });
+    c.bench_function("torrent name tape", |b| {
+        b.iter(|| {
+            let tape = Tape::parse(black_box(&torrent)).unwrap();
+            let name = tape.root().get(b"info").unwrap().get(b"name").unwrap();
+            name.as_bytes().unwrap().len()
+        })
+    });
+    c.bench_function("torrent name value", |b| {
+        b.iter(|| {
+            let value: Value = bencode::from_bytes(black_box(&torrent)).unwrap();
+            value
+                .pointer("/info/name")
+                .unwrap()
+                .as_bytes()
+                .unwrap()
+                .len()
+        })
+    });
+}
+
+criterion_group!(benches, total_length);
+criterion_main!(benches);
@@ -0,0 +1,20 @@
+#![no_main]
+
+use bencode::{Decoder, Status};
+use libfuzzer_sys::fuzz_target;
+use serde::de::IgnoredAny;
+
+fuzz_target!(|data: &[u8]| {
+    let mut decoder = Decoder::new();
+    for chunk in data.chunks(7) {
+        match decoder.feed(chunk) {
+            Ok(Status::Complete { len }) => {
+                assert!(len <= decoder.buffer().len());
+                let _ = decoder.value::<IgnoredAny>();
+                decoder.take();
+            }
+            Ok(Status::NeedMore { at_least }) => assert!(at_least > 0),
+            Err(_) => return,
+        }
+    }
+});
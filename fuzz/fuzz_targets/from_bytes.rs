@@ -0,0 +1,47 @@
+#![no_main]
+
+use std::collections::BTreeMap;
+
+use bencode::utils::binary_string;
+use libfuzzer_sys::fuzz_target;
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+#[allow(dead_code)]
+struct Message {
+    #[serde(with = "binary_string")]
+    t: Vec<u8>,
+    y: String,
+    q: Option<String>,
+    a: Option<BTreeMap<String, IgnoredAny>>,
+    r: Option<BTreeMap<String, IgnoredAny>>,
+    e: Option<(i64, String)>,
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(dead_code)]
+enum Kind {
+    Unit,
+    Newtype(u8),
+    Tuple(i8, char),
+    Struct { id: u32 },
+}
+
+fuzz_target!(|data: &[u8]| {
+    let _ = bencode::from_bytes::<IgnoredAny>(data);
+    let _ = bencode::from_bytes::<bencode::Value>(data);
+    let _ = bencode::from_bytes::<Message>(data);
+    let _ = bencode::from_bytes::<Vec<Kind>>(data);
+    let _ = bencode::from_bytes::<BTreeMap<String, Vec<u64>>>(data);
+    if let Ok(tape) = bencode::tape::Tape::parse(data) {
+        for (_, value) in tape.root().entries() {
+            let _ = value.iter().count();
+        }
+    }
+    for value in bencode::Deserializer::from_bytes(data).into_iter::<IgnoredAny>() {
+        if value.is_err() {
+            break;
+        }
+    }
+});
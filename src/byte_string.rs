@@ -0,0 +1,207 @@
+use std::borrow::Borrow;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use serde::de::{Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use crate::value::{DebugBytes, Value};
+
+/// An owned bencode byte string.
+///
+/// Unlike a bare `Vec<u8>` it serializes as a byte string without
+/// `#[serde(with = "...")]`, so it also works as a map key and inside
+/// `Option` or `Vec`. Debug output shows printable UTF-8 as text and anything
+/// else as hex.
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteString(Vec<u8>);
+
+impl ByteString {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn into_vec(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl fmt::Debug for ByteString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&DebugBytes(&self.0), f)
+    }
+}
+
+impl Deref for ByteString {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl DerefMut for ByteString {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+}
+
+impl AsRef<[u8]> for ByteString {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Borrow<[u8]> for ByteString {
+    fn borrow(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for ByteString {
+    fn from(bytes: Vec<u8>) -> Self {
+        ByteString(bytes)
+    }
+}
+
+impl From<&[u8]> for ByteString {
+    fn from(bytes: &[u8]) -> Self {
+        ByteString(bytes.to_vec())
+    }
+}
+
+impl<const N: usize> From<&[u8; N]> for ByteString {
+    fn from(bytes: &[u8; N]) -> Self {
+        ByteString(bytes.to_vec())
+    }
+}
+
+impl From<&str> for ByteString {
+    fn from(string: &str) -> Self {
+        ByteString(string.as_bytes().to_vec())
+    }
+}
+
+impl From<String> for ByteString {
+    fn from(string: String) -> Self {
+        ByteString(string.into_bytes())
+    }
+}
+
+impl From<ByteString> for Vec<u8> {
+    fn from(bytes: ByteString) -> Self {
+        bytes.0
+    }
+}
+
+impl From<ByteString> for Value {
+    fn from(bytes: ByteString) -> Self {
+        Value::Bytes(bytes.0)
+    }
+}
+
+impl Serialize for ByteString {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+struct ByteStringVisitor;
+
+impl<'de> Visitor<'de> for ByteStringVisitor {
+    type Value = ByteString;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a byte string")
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(ByteString::from(v))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(ByteString(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(ByteString::from(v))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Self::Value, E> {
+        Ok(ByteString::from(v))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(ByteString(bytes))
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteString {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_byte_buf(ByteStringVisitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{from_bytes, from_value, to_bytes, to_value};
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Ping {
+        id: ByteString,
+        token: Option<ByteString>,
+        values: Vec<ByteString>,
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let input = b"d2:id2:\xeb\x8b6:valuesl6:peer_11:\x00ee";
+        let ping: Ping = from_bytes(input).unwrap();
+        assert_eq!(
+            ping,
+            Ping {
+                id: b"\xeb\x8b".into(),
+                token: None,
+                values: vec!["peer_1".into(), vec![0].into()],
+            }
+        );
+        assert_eq!(to_bytes(&ping).unwrap(), input);
+        assert_eq!(from_value::<Ping>(to_value(&ping).unwrap()).unwrap(), ping);
+        assert_eq!(&ping.id[..], b"\xeb\x8b");
+        assert_eq!(ping.values[0].len(), 6);
+    }
+
+    #[test]
+    fn test_map_key() {
+        let input = b"d4:porti2e2:\xeb\x8bi1ee";
+        let map: BTreeMap<ByteString, i64> = from_bytes(input).unwrap();
+        assert_eq!(map[&b"\xeb\x8b"[..]], 1);
+        assert_eq!(map[&b"port"[..]], 2);
+        assert_eq!(to_bytes(&map).unwrap(), input);
+    }
+
+    #[test]
+    fn test_debug() {
+        let ping: Ping = from_bytes(b"d2:id2:\xeb\x8b5:token2:aa6:valueslee").unwrap();
+        assert_eq!(
+            format!("{:?}", ping),
+            r#"Ping { id: 0xeb8b, token: Some("aa"), values: [] }"#
+        );
+    }
+}
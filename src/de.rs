@@ -1,176 +1,274 @@
-use num_traits::ops::checked::{CheckedAdd, CheckedMul};
+use std::marker::PhantomData;
+
+use num_traits::FromPrimitive;
 
 use serde::de::{
-    self, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess,
-    Visitor,
+    self, value::BorrowedStrDeserializer, DeserializeSeed, EnumAccess, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
 };
 
 use crate::error::Error;
-
-pub struct Deserializer<'de> {
-    input: &'de [u8],
+use crate::raw;
+use crate::spanned::{self, SpannedAccess};
+
+/// Maximum nesting of lists and dictionaries accepted by the [`Deserializer`].
+pub const MAX_DEPTH: usize = 128;
+
+/// Open lists and dictionaries of a value scanned without recursion, up to
+/// [`MAX_DEPTH`] levels. Bit `n` of `dicts` is set when the container at depth
+/// `n` is a dictionary, and bit `n` of `values` when that dictionary has read
+/// a key and is waiting for its value.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Nesting {
+    depth: usize,
+    dicts: u128,
+    values: u128,
 }
 
-impl<'de> Deserializer<'de> {
-    pub fn from_bytes(input: &'de [u8]) -> Self {
-        Self { input }
+const _: () = assert!(MAX_DEPTH <= u128::BITS as usize);
+
+impl Nesting {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.depth == 0
+    }
+
+    /// Whether a token starting with `byte` may come next: dictionary keys
+    /// must be byte strings and a dictionary may not end after a key.
+    pub(crate) fn allows(&self, byte: u8) -> bool {
+        let Some(level) = self.depth.checked_sub(1) else {
+            return byte != b'e';
+        };
+        let bit = 1u128 << level;
+        match (self.dicts & bit != 0, self.values & bit != 0) {
+            (false, _) => true,
+            (true, false) => byte == b'e' || byte.is_ascii_digit(),
+            (true, true) => byte != b'e',
+        }
     }
-    fn peek_byte(&self) -> Result<u8, Error> {
-        self.input.get(0).map(|v| v.clone()).ok_or(Error::Eof)
+
+    /// Records a key or value at the current level.
+    pub(crate) fn item(&mut self) {
+        if let Some(level) = self.depth.checked_sub(1) {
+            self.values ^= (1u128 << level) & self.dicts;
+        }
     }
 
-    fn next_byte(&mut self) -> Result<u8, Error> {
-        let ch = self.peek_byte()?;
-        self.input = &self.input[1..];
+    pub(crate) fn open(&mut self, dict: bool) -> Result<(), Error> {
+        if self.depth == MAX_DEPTH {
+            return Err(Error::RecursionLimitExceeded);
+        }
+        self.item();
+        let bit = 1u128 << self.depth;
+        if dict {
+            self.dicts |= bit;
+        } else {
+            self.dicts &= !bit;
+        }
+        self.values &= !bit;
+        self.depth += 1;
+        Ok(())
+    }
 
-        Ok(ch)
+    pub(crate) fn close(&mut self) {
+        self.depth -= 1;
     }
-    fn parse_signed<T>(&mut self) -> Result<T, Error>
+}
+
+/// Whether a run of digits starts with a zero that BEP 3 forbids: any zero
+/// after a minus sign, or a zero followed by more digits.
+pub(crate) fn has_leading_zero(digits: &[u8], negative: bool) -> bool {
+    digits.first() == Some(&b'0')
+        && (negative || digits.get(1).is_some_and(|ch| ch.is_ascii_digit()))
+}
+
+/// Deserializer reading bencode from a byte slice.
+///
+/// Deserialization never panics: malformed, truncated or excessively nested
+/// input is reported as an [`Error`]. This guarantee is exercised by the
+/// fuzz targets in the `fuzz` directory.
+pub struct Deserializer<'de> {
+    origin: &'de [u8],
+    input: &'de [u8],
+    remaining_depth: usize,
+}
+
+/// Integer as read from the input, before it is narrowed to the requested type.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Integer {
+    Negative(i64),
+    Positive(u64),
+}
+
+impl From<i64> for Integer {
+    fn from(integer: i64) -> Self {
+        match u64::try_from(integer) {
+            Ok(integer) => Integer::Positive(integer),
+            Err(_) => Integer::Negative(integer),
+        }
+    }
+}
+
+impl Integer {
+    /// Visits the integer with the narrowest type it fits in.
+    pub(crate) fn visit<'de, V>(self, visitor: V) -> Result<V::Value, Error>
     where
-        T: CheckedAdd + CheckedMul + From<i8>,
+        V: Visitor<'de>,
     {
-        if self.next_byte()? != b'i' {
-            return Err(Error::ExpectedInteger);
-        }
-        let is_positive = if self.peek_byte()? != b'-' {
-            T::from(1)
-        } else {
-            self.input = &self.input[1..];
-            T::from(-1)
-        };
-        let mut integer = match self.next_byte()? {
-            ch @ b'0'..=b'9' => T::from(
-                (ch as i8)
-                    .checked_sub(b'0' as i8)
-                    .ok_or(Error::LargeNumber)?,
-            ),
-            _ => {
-                return Err(Error::ExpectedInteger);
-            }
-        };
-        let mut counter: usize = 0;
-        let mut input_iterator = self.input.iter();
-        let mut closing_tag_found = false;
-        while let Some(char) = input_iterator.next() {
-            match char {
-                ch @ b'0'..=b'9' => {
-                    counter += 1;
-                    integer = integer
-                        .checked_mul(&T::from(10))
-                        .ok_or(Error::LargeNumber)?;
-                    integer = integer
-                        .checked_add(&T::from(
-                            (*ch as i8)
-                                .checked_sub(b'0' as i8)
-                                .ok_or(Error::LargeNumber)?,
-                        ))
-                        .ok_or(Error::LargeNumber)?;
-                }
-                b'e' => {
-                    counter += 1;
-                    closing_tag_found = true;
-                    break;
+        match self {
+            Integer::Negative(integer) => {
+                if let Ok(integer) = i8::try_from(integer) {
+                    visitor.visit_i8(integer)
+                } else if let Ok(integer) = i16::try_from(integer) {
+                    visitor.visit_i16(integer)
+                } else if let Ok(integer) = i32::try_from(integer) {
+                    visitor.visit_i32(integer)
+                } else {
+                    visitor.visit_i64(integer)
                 }
-                _ => {
-                    return Err(Error::ExpectedInteger);
+            }
+            Integer::Positive(integer) => {
+                if let Ok(integer) = u8::try_from(integer) {
+                    visitor.visit_u8(integer)
+                } else if let Ok(integer) = u16::try_from(integer) {
+                    visitor.visit_u16(integer)
+                } else if let Ok(integer) = u32::try_from(integer) {
+                    visitor.visit_u32(integer)
+                } else {
+                    visitor.visit_u64(integer)
                 }
             }
         }
-        if !closing_tag_found {
-            return Err(Error::ClosingTagNotFound);
-        }
+    }
+}
 
-        self.input = &self.input[counter..];
-        Ok(integer * is_positive)
+impl<'de> Deserializer<'de> {
+    pub fn from_bytes(input: &'de [u8]) -> Self {
+        Self {
+            origin: input,
+            input,
+            remaining_depth: MAX_DEPTH,
+        }
     }
 
-    fn parse_unsigned<T>(&mut self) -> Result<T, Error>
+    /// Turns the deserializer into an iterator over concatenated values of type `T`.
+    #[allow(clippy::should_implement_trait)]
+    pub fn into_iter<T>(self) -> StreamDeserializer<'de, T>
     where
-        T: CheckedAdd + CheckedMul + From<u8>,
+        T: de::Deserialize<'de>,
     {
+        StreamDeserializer {
+            de: self,
+            failed: false,
+            output: PhantomData,
+        }
+    }
+
+    /// Number of bytes consumed so far.
+    pub fn position(&self) -> usize {
+        self.origin.len() - self.input.len()
+    }
+
+    /// Input that has not been consumed yet.
+    pub fn remaining(&self) -> &'de [u8] {
+        self.input
+    }
+
+    /// Checks that the whole input has been consumed.
+    pub fn end(&self) -> Result<(), Error> {
+        if self.input.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::TrailingBytes)
+        }
+    }
+
+    pub(crate) fn peek_byte(&self) -> Result<u8, Error> {
+        self.input.first().copied().ok_or(Error::Eof)
+    }
+
+    pub(crate) fn next_byte(&mut self) -> Result<u8, Error> {
+        let ch = self.peek_byte()?;
+        self.input = &self.input[1..];
+
+        Ok(ch)
+    }
+    pub(crate) fn parse_integer(&mut self) -> Result<Integer, Error> {
         if self.next_byte()? != b'i' {
             return Err(Error::ExpectedInteger);
         }
-        if self.peek_byte()? == b'-' {
-            return Err(Error::ExpectedUnsignedInteger);
+        let negative = self.peek_byte()? == b'-';
+        if negative {
+            self.next_byte()?;
         }
-        let mut integer = match self.next_byte()? {
-            ch @ b'0'..=b'9' => T::from(ch as u8 - b'0'),
-            _ => {
-                return Err(Error::ExpectedInteger);
-            }
-        };
-        let mut counter: usize = 0;
-        let mut input_iterator = self.input.iter();
-        let mut closing_tag_found = false;
-        while let Some(char) = input_iterator.next() {
-            match char {
+        let mut magnitude: u64 = 0;
+        let mut digits: usize = 0;
+        loop {
+            match self.next_byte().or(Err(Error::ClosingTagNotFound))? {
+                // BEP 3 forbids leading zeros, other than in `i0e` itself.
+                b'0'..=b'9' if digits > 0 && magnitude == 0 => {
+                    return Err(Error::ExpectedInteger);
+                }
                 ch @ b'0'..=b'9' => {
-                    counter += 1;
-                    integer = integer
-                        .checked_mul(&T::from(10))
-                        .ok_or(Error::LargeNumber)?;
-                    integer = integer
-                        .checked_add(&T::from(
-                            (*ch as u8).checked_sub(b'0').ok_or(Error::LargeNumber)?,
-                        ))
+                    digits += 1;
+                    magnitude = magnitude
+                        .checked_mul(10)
+                        .and_then(|magnitude| magnitude.checked_add(u64::from(ch - b'0')))
                         .ok_or(Error::LargeNumber)?;
                 }
-                b'e' => {
-                    counter += 1;
-                    closing_tag_found = true;
-                    break;
-                }
+                b'e' if digits > 0 => break,
                 _ => {
                     return Err(Error::ExpectedInteger);
                 }
             }
         }
-        if !closing_tag_found {
-            return Err(Error::ClosingTagNotFound);
-        }
 
-        self.input = &self.input[counter..];
-        Ok(integer)
+        if negative && magnitude == 0 {
+            Err(Error::ExpectedInteger)
+        } else if negative {
+            0i64.checked_sub_unsigned(magnitude)
+                .map(Integer::Negative)
+                .ok_or(Error::LargeNumber)
+        } else {
+            Ok(Integer::Positive(magnitude))
+        }
     }
 
-    fn parse_byte(&mut self) -> Result<u8, Error> {
-        match self.next_byte()? {
-            b'1' => 1usize,
-            _ => {
-                return Err(Error::ExpectedInteger);
-            }
-        };
-        match self.next_byte()? {
-            b':' => {}
-            _ => {
-                return Err(Error::ClosingTagNotFound);
-            }
-        };
-        let char = self.next_byte()?;
-
-        Ok(char)
+    fn parse_int<T>(&mut self) -> Result<T, Error>
+    where
+        T: FromPrimitive,
+    {
+        match self.parse_integer()? {
+            Integer::Negative(integer) => T::from_i64(integer).ok_or_else(|| {
+                if T::from_i8(-1).is_none() {
+                    Error::ExpectedUnsignedInteger
+                } else {
+                    Error::LargeNumber
+                }
+            }),
+            Integer::Positive(integer) => T::from_u64(integer).ok_or(Error::LargeNumber),
+        }
     }
 
-    fn parse_byte_string(&mut self) -> Result<Vec<u8>, Error> {
+    pub(crate) fn parse_byte_string_borrowed(&mut self) -> Result<&'de [u8], Error> {
         let mut size: usize = match self.next_byte()? {
-            ch @ b'1'..=b'9' => usize::from(ch as u8 - b'0'),
+            ch @ b'0'..=b'9' => usize::from(ch - b'0'),
             _ => {
                 return Err(Error::ExpectedInteger);
             }
         };
         let mut counter: usize = 0;
-        let mut input_iterator = self.input.iter();
         let mut closing_tag_found = false;
-        while let Some(char) = input_iterator.next() {
+        for char in self.input.iter() {
             match char {
+                // A length may only start with 0 if it is 0.
+                b'0'..=b'9' if size == 0 => {
+                    return Err(Error::ExpectedInteger);
+                }
                 ch @ b'0'..=b'9' => {
                     counter += 1;
-                    size = size.checked_mul(10).ok_or(Error::LargeNumber)?;
                     size = size
-                        .checked_add(
-                            (*ch as u8).checked_sub(b'0').ok_or(Error::LargeNumber)? as usize
-                        )
+                        .checked_mul(10)
+                        .and_then(|size| size.checked_add(usize::from(ch - b'0')))
                         .ok_or(Error::LargeNumber)?;
                 }
                 b':' => {
@@ -191,61 +289,108 @@ impl<'de> Deserializer<'de> {
         if self.input.len() < size {
             return Err(Error::BadStringSize);
         }
-        let to_return = Vec::from_iter(self.input[..size].iter().cloned());
+        let to_return = &self.input[..size];
 
         self.input = &self.input[size..];
 
         Ok(to_return)
     }
 
-    fn parse_byte_string_borrowed(&mut self) -> Result<&'de [u8], Error> {
-        let mut size: usize = match self.next_byte()? {
-            ch @ b'1'..=b'9' => usize::from(ch as u8 - b'0'),
-            _ => {
-                return Err(Error::ExpectedInteger);
+    /// Skips over the next value without visiting it. Strings are jumped over
+    /// by their length prefix and containers are tracked with a depth counter,
+    /// so nothing is allocated and nesting does not recurse. Dictionaries are
+    /// checked the same way [`Tape::parse`](crate::tape::Tape::parse) checks
+    /// them: every key must be a byte string and must be followed by a value.
+    fn skip_value(&mut self) -> Result<(), Error> {
+        let mut nesting = Nesting::default();
+        loop {
+            let start = self.position();
+            let byte = self.peek_byte()?;
+            if !nesting.allows(byte) {
+                return Err(Error::Syntax(start));
             }
-        };
-        let mut counter: usize = 0;
-        let mut input_iterator = self.input.iter();
-        let mut closing_tag_found = false;
-        while let Some(char) = input_iterator.next() {
-            match char {
-                ch @ b'0'..=b'9' => {
-                    counter += 1;
-                    size = size.checked_mul(10).ok_or(Error::LargeNumber)?;
-                    size = size
-                        .checked_add(
-                            (*ch as u8).checked_sub(b'0').ok_or(Error::LargeNumber)? as usize
-                        )
-                        .ok_or(Error::LargeNumber)?;
+            match byte {
+                b'i' => {
+                    self.skip_integer()?;
+                    nesting.item();
                 }
-                b':' => {
-                    counter += 1;
-                    closing_tag_found = true;
-                    break;
+                b'0'..=b'9' => {
+                    self.parse_byte_string_borrowed()?;
+                    nesting.item();
                 }
-                _ => {
-                    return Err(Error::ExpectedInteger);
+                b'l' | b'd' => {
+                    nesting.open(byte == b'd')?;
+                    self.next_byte()?;
                 }
+                b'e' => {
+                    self.next_byte()?;
+                    nesting.close();
+                }
+                _ => return Err(Error::Syntax(start)),
+            }
+            if nesting.is_empty() {
+                return Ok(());
             }
         }
-        if !closing_tag_found {
-            return Err(Error::ClosingTagNotFound);
+    }
+
+    fn skip_integer(&mut self) -> Result<(), Error> {
+        let body = &self.input[1..];
+        let digits = body.strip_prefix(b"-").unwrap_or(body);
+        if has_leading_zero(digits, digits.len() < body.len()) {
+            return Err(Error::ExpectedInteger);
         }
-        self.input = &self.input[counter..];
+        match digits.iter().position(|ch| !ch.is_ascii_digit()) {
+            Some(index) if index > 0 && digits[index] == b'e' => {
+                self.input = &digits[index + 1..];
+                Ok(())
+            }
+            Some(_) => Err(Error::ExpectedInteger),
+            None => Err(Error::ClosingTagNotFound),
+        }
+    }
 
-        if self.input.len() < size {
-            return Err(Error::BadStringSize);
+    /// Visits a byte string as borrowed `str` when it is valid UTF-8 and as
+    /// borrowed bytes otherwise.
+    fn visit_byte_string<V>(&mut self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        let parsed_string = self.parse_byte_string_borrowed()?;
+        match std::str::from_utf8(parsed_string) {
+            Ok(parsed_string) => visitor.visit_borrowed_str(parsed_string),
+            Err(_) => visitor.visit_borrowed_bytes(parsed_string),
         }
-        let to_return = &self.input[..size];
+    }
 
-        self.input = &self.input[size..];
+    /// Deserializes an element that must consume input, so that element types
+    /// which read nothing (like `()`) can't loop forever inside a list.
+    fn deserialize_consuming<T>(&mut self, seed: T) -> Result<T::Value, Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let position = self.position();
+        let value = seed.deserialize(&mut *self)?;
+        if self.position() == position {
+            return Err(Error::Syntax(position));
+        }
+        Ok(value)
+    }
 
-        Ok(to_return)
+    fn enter(&mut self) -> Result<(), Error> {
+        self.remaining_depth = self
+            .remaining_depth
+            .checked_sub(1)
+            .ok_or(Error::RecursionLimitExceeded)?;
+        Ok(())
+    }
+
+    fn leave(&mut self) {
+        self.remaining_depth += 1;
     }
 }
 
-impl<'de, 'a> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
+impl<'de> serde::Deserializer<'de> for &mut Deserializer<'de> {
     type Error = Error;
 
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -253,23 +398,11 @@ impl<'de, 'a> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
         V: Visitor<'de>,
     {
         match self.peek_byte()? {
-            b'i' => {
-                if let Some(sign) = self.input.get(1) {
-                    if sign.eq(&b'-') {
-                        self.deserialize_i64(visitor)
-                    } else {
-                        self.deserialize_u64(visitor)
-                    }
-                } else {
-                    Err(Error::Syntax)
-                }
-            }
-            b'1'..=b'9' => self.deserialize_str(visitor),
+            b'i' => self.parse_integer()?.visit(visitor),
+            b'0'..=b'9' => self.visit_byte_string(visitor),
             b'd' => self.deserialize_map(visitor),
             b'l' => self.deserialize_seq(visitor),
-            _ => {
-                todo!()
-            }
+            _ => Err(Error::Syntax(self.position())),
         }
     }
 
@@ -277,7 +410,7 @@ impl<'de, 'a> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        let num = self.parse_unsigned::<u8>()?;
+        let num = self.parse_int::<u8>()?;
         if num == 0 {
             visitor.visit_bool(false)
         } else if num == 1 {
@@ -291,66 +424,66 @@ impl<'de, 'a> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        visitor.visit_i8(self.parse_signed()?)
+        visitor.visit_i8(self.parse_int()?)
     }
 
     fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_i16(self.parse_signed()?)
+        visitor.visit_i16(self.parse_int()?)
     }
 
     fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_i32(self.parse_signed()?)
+        visitor.visit_i32(self.parse_int()?)
     }
 
     fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_i64(self.parse_signed()?)
+        visitor.visit_i64(self.parse_int()?)
     }
 
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_u8(self.parse_unsigned()?)
+        visitor.visit_u8(self.parse_int()?)
     }
 
     fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_u16(self.parse_unsigned()?)
+        visitor.visit_u16(self.parse_int()?)
     }
 
     fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_u32(self.parse_unsigned()?)
+        visitor.visit_u32(self.parse_int()?)
     }
 
     fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_u64(self.parse_unsigned()?)
+        visitor.visit_u64(self.parse_int()?)
     }
 
-    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_f32<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
         Err(Error::InvalidValue("Cannot deserialize f32"))
     }
 
-    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_f64<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
@@ -361,57 +494,45 @@ impl<'de, 'a> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        let parsed_string = self.parse_byte_string()?;
+        let parsed_string = self.parse_byte_string_borrowed()?;
         if parsed_string.len() > 4 {
             return Err(Error::TooBigChar);
         }
-        let num: u32 = match parsed_string.len() {
-            1 => parsed_string[0] as u32,
-            2 => (parsed_string[0] as u32) << 8 | parsed_string[1] as u32,
-            3 => {
-                (parsed_string[0] as u32) << 16
-                    | (parsed_string[1] as u32) << 8
-                    | parsed_string[2] as u32
-            }
-            4 => {
-                (parsed_string[0] as u32) << 24
-                    | (parsed_string[1] as u32) << 16
-                    | (parsed_string[2] as u32) << 8
-                    | parsed_string[3] as u32
-            }
-            _ => 0, // impossible to get this branch
-        };
-        visitor.visit_char(unsafe { char::from_u32_unchecked(num) })
+        let mut chars = std::str::from_utf8(parsed_string)
+            .map_err(|_| Error::ExpectedString)?
+            .chars();
+        match (chars.next(), chars.next()) {
+            (Some(char), None) => visitor.visit_char(char),
+            _ => Err(Error::InvalidValue("Expected a single character")),
+        }
     }
 
     fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        let parsed_string = self.parse_byte_string_borrowed()?;
-        visitor.visit_str(unsafe { std::str::from_utf8_unchecked(parsed_string) })
+        self.visit_byte_string(visitor)
     }
 
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        let parsed_string = self.parse_byte_string()?;
-        visitor.visit_string(unsafe { String::from_utf8_unchecked(parsed_string) })
+        self.visit_byte_string(visitor)
     }
 
     fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_bytes(self.parse_byte_string_borrowed()?)
+        visitor.visit_borrowed_bytes(self.parse_byte_string_borrowed()?)
     }
 
     fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_byte_buf(self.parse_byte_string()?)
+        visitor.visit_borrowed_bytes(self.parse_byte_string_borrowed()?)
     }
 
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -430,7 +551,7 @@ impl<'de, 'a> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
 
     fn deserialize_unit_struct<V>(
         self,
-        name: &'static str,
+        _name: &'static str,
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
@@ -447,6 +568,11 @@ impl<'de, 'a> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
+        if name == raw::TOKEN {
+            let start = self.position();
+            self.skip_value()?;
+            return visitor.visit_borrowed_bytes(&self.origin[start..self.position()]);
+        }
         visitor.visit_newtype_struct(self)
     }
 
@@ -455,7 +581,10 @@ impl<'de, 'a> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
         V: Visitor<'de>,
     {
         if self.next_byte()? == b'l' {
-            let value = visitor.visit_seq(&mut self)?;
+            self.enter()?;
+            let value = visitor.visit_seq(&mut self);
+            self.leave();
+            let value = value?;
             if self.next_byte()? == b'e' {
                 Ok(value)
             } else {
@@ -466,7 +595,7 @@ impl<'de, 'a> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
         }
     }
 
-    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
@@ -475,8 +604,8 @@ impl<'de, 'a> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
 
     fn deserialize_tuple_struct<V>(
         self,
-        name: &'static str,
-        len: usize,
+        _name: &'static str,
+        _len: usize,
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
@@ -490,7 +619,10 @@ impl<'de, 'a> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
         V: Visitor<'de>,
     {
         if self.next_byte()? == b'd' {
-            let value = visitor.visit_map(&mut self)?;
+            self.enter()?;
+            let value = visitor.visit_map(&mut self);
+            self.leave();
+            let value = value?;
             if self.next_byte()? == b'e' {
                 Ok(value)
             } else {
@@ -504,31 +636,38 @@ impl<'de, 'a> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     fn deserialize_struct<V>(
         self,
         name: &'static str,
-        fields: &'static [&'static str],
+        _fields: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
+        if name == spanned::NAME {
+            return visitor.visit_map(SpannedAccess::new(self));
+        }
         self.deserialize_map(visitor)
     }
 
     fn deserialize_enum<V>(
         self,
-        name: &'static str,
-        variants: &'static [&'static str],
+        _name: &'static str,
+        _variants: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
         match self.peek_byte()? {
-            b'1'..=b'9' => visitor.visit_enum(unsafe {
-                String::from_utf8_unchecked(self.parse_byte_string()?).into_deserializer()
-            }),
+            b'0'..=b'9' => visitor.visit_enum(BorrowedStrDeserializer::new(
+                std::str::from_utf8(self.parse_byte_string_borrowed()?)
+                    .map_err(|_| Error::ExpectedString)?,
+            )),
             b'd' => {
                 self.next_byte()?;
-                let value = visitor.visit_enum(Enum::new(self))?;
+                self.enter()?;
+                let value = visitor.visit_enum(Enum::new(self));
+                self.leave();
+                let value = value?;
                 if self.next_byte()? == b'e' {
                     Ok(value)
                 } else {
@@ -543,18 +682,19 @@ impl<'de, 'a> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        self.deserialize_string(visitor)
+        self.visit_byte_string(visitor)
     }
 
     fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_any(visitor)
+        self.skip_value()?;
+        visitor.visit_unit()
     }
 }
 
-impl<'de, 'a> SeqAccess<'de> for Deserializer<'de> {
+impl<'de> SeqAccess<'de> for Deserializer<'de> {
     type Error = Error;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
@@ -564,11 +704,11 @@ impl<'de, 'a> SeqAccess<'de> for Deserializer<'de> {
         if self.peek_byte()? == b'e' {
             return Ok(None);
         }
-        seed.deserialize(&mut *self).map(Some)
+        self.deserialize_consuming(seed).map(Some)
     }
 }
 
-impl<'de, 'a> MapAccess<'de> for Deserializer<'de> {
+impl<'de> MapAccess<'de> for Deserializer<'de> {
     type Error = Error;
 
     fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
@@ -578,7 +718,7 @@ impl<'de, 'a> MapAccess<'de> for Deserializer<'de> {
         if self.peek_byte()? == b'e' {
             return Ok(None);
         }
-        seed.deserialize(&mut *self).map(Some)
+        self.deserialize_consuming(seed).map(Some)
     }
 
     fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
@@ -628,7 +768,7 @@ impl<'de, 'a> VariantAccess<'de> for Enum<'a, 'de> {
         seed.deserialize(&mut *self.de)
     }
 
-    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
@@ -637,7 +777,7 @@ impl<'de, 'a> VariantAccess<'de> for Enum<'a, 'de> {
 
     fn struct_variant<V>(
         self,
-        fields: &'static [&'static str],
+        _fields: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
@@ -647,6 +787,51 @@ impl<'de, 'a> VariantAccess<'de> for Enum<'a, 'de> {
     }
 }
 
+/// Iterator over a sequence of concatenated bencode values.
+///
+/// Every item carries the byte offset at which the value starts. Iteration
+/// stops after the input is exhausted or after the first error.
+pub struct StreamDeserializer<'de, T> {
+    de: Deserializer<'de>,
+    failed: bool,
+    output: PhantomData<T>,
+}
+
+impl<'de, T> StreamDeserializer<'de, T>
+where
+    T: de::Deserialize<'de>,
+{
+    pub fn new(input: &'de [u8]) -> Self {
+        Deserializer::from_bytes(input).into_iter()
+    }
+
+    /// Offset of the first byte that has not been consumed yet.
+    pub fn byte_offset(&self) -> usize {
+        self.de.position()
+    }
+}
+
+impl<'de, T> Iterator for StreamDeserializer<'de, T>
+where
+    T: de::Deserialize<'de>,
+{
+    type Item = Result<(usize, T), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.de.input.is_empty() {
+            return None;
+        }
+        let offset = self.de.position();
+        match T::deserialize(&mut self.de) {
+            Ok(value) => Some(Ok((offset, value))),
+            Err(err) => {
+                self.failed = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
 use serde::Deserialize;
 pub fn from_bytes<'a, T>(b: &'a [u8]) -> Result<T, Error>
 where
@@ -654,11 +839,19 @@ where
 {
     let mut deserializer = Deserializer::from_bytes(b);
     let t = T::deserialize(&mut deserializer)?;
-    if deserializer.input.is_empty() {
-        Ok(t)
-    } else {
-        Err(Error::TrailingBytes)
-    }
+    deserializer.end()?;
+    Ok(t)
+}
+
+/// Deserializes a value from the start of `b` and returns it with the bytes
+/// that follow it, e.g. the raw piece data of a BEP 9 `ut_metadata` message.
+pub fn from_bytes_prefix<'a, T>(b: &'a [u8]) -> Result<(T, &'a [u8]), Error>
+where
+    T: Deserialize<'a>,
+{
+    let mut deserializer = Deserializer::from_bytes(b);
+    let t = T::deserialize(&mut deserializer)?;
+    Ok((t, deserializer.remaining()))
 }
 
 #[cfg(test)]
@@ -668,7 +861,7 @@ mod test {
     use serde::{self, Deserialize};
     #[test]
     fn test() {
-        #[derive(Deserialize, Debug)]
+        #[derive(Deserialize, Debug, PartialEq)]
         pub struct AnnouncePeer {
             id: String,
             info_hash: String,
@@ -681,12 +874,20 @@ mod test {
         let mut deserializer = Deserializer::from_bytes(string_test.as_bytes());
         let t = AnnouncePeer::deserialize(&mut deserializer).unwrap();
 
-        println!("{:?}", t);
+        assert_eq!(
+            t,
+            AnnouncePeer {
+                id: "picked id".into(),
+                info_hash: "info hash".into(),
+                port: 228,
+                implied_port: true,
+            }
+        );
     }
 
     #[test]
     fn test_with() {
-        #[derive(Deserialize, Debug)]
+        #[derive(Deserialize, Debug, PartialEq)]
         pub struct AnnouncePeer {
             #[serde(deserialize_with = "binary_string::deserialize")]
             id: Vec<u8>,
@@ -699,6 +900,352 @@ mod test {
         let mut deserializer = Deserializer::from_bytes(string_test.as_bytes());
         let t = AnnouncePeer::deserialize(&mut deserializer).unwrap();
 
-        println!("{:?}", t);
+        assert_eq!(
+            t,
+            AnnouncePeer {
+                id: vec![1, 2, 3, 4, 5, 6],
+                info_hash: "info hash".into(),
+                port: 228,
+                implied_port: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_stream() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Ping {
+            id: u32,
+        }
+
+        let input = b"d2:idi1eed2:idi2ee4:spam";
+        let mut stream = Deserializer::from_bytes(input).into_iter::<Ping>();
+
+        assert_eq!(stream.next().unwrap().unwrap(), (0, Ping { id: 1 }));
+        assert_eq!(stream.next().unwrap().unwrap(), (9, Ping { id: 2 }));
+        assert_eq!(stream.byte_offset(), 18);
+        assert!(stream.next().unwrap().is_err());
+        assert!(stream.next().is_none());
+    }
+
+    #[test]
+    fn test_integer_ranges() {
+        assert_eq!(
+            from_bytes::<i64>(b"i-9223372036854775808e").unwrap(),
+            i64::MIN
+        );
+        assert_eq!(
+            from_bytes::<i64>(b"i9223372036854775807e").unwrap(),
+            i64::MAX
+        );
+        assert_eq!(
+            from_bytes::<u64>(b"i18446744073709551615e").unwrap(),
+            u64::MAX
+        );
+        assert_eq!(from_bytes::<i8>(b"i-128e").unwrap(), i8::MIN);
+        assert_eq!(from_bytes::<i8>(b"i127e").unwrap(), i8::MAX);
+        assert_eq!(from_bytes::<i32>(b"i0e").unwrap(), 0);
+
+        assert!(matches!(
+            from_bytes::<i8>(b"i-129e"),
+            Err(Error::LargeNumber)
+        ));
+        assert!(matches!(
+            from_bytes::<i8>(b"i128e"),
+            Err(Error::LargeNumber)
+        ));
+        assert!(matches!(
+            from_bytes::<i64>(b"i-9223372036854775809e"),
+            Err(Error::LargeNumber)
+        ));
+        assert!(matches!(
+            from_bytes::<u64>(b"i18446744073709551616e"),
+            Err(Error::LargeNumber)
+        ));
+        assert!(matches!(
+            from_bytes::<u8>(b"i-1e"),
+            Err(Error::ExpectedUnsignedInteger)
+        ));
+        assert!(matches!(
+            from_bytes::<i32>(b"i-e"),
+            Err(Error::ExpectedInteger)
+        ));
+        assert!(matches!(
+            from_bytes::<i32>(b"ie"),
+            Err(Error::ExpectedInteger)
+        ));
+        assert!(matches!(
+            from_bytes::<i32>(b"i12"),
+            Err(Error::ClosingTagNotFound)
+        ));
+        for malformed in [&b"i-0e"[..], b"i03e", b"i00e", b"i-03e"] {
+            assert!(matches!(
+                from_bytes::<i64>(malformed),
+                Err(Error::ExpectedInteger)
+            ));
+            assert!(from_bytes::<de::IgnoredAny>(malformed).is_err());
+        }
+        assert_eq!(from_bytes::<&str>(b"0:").unwrap(), "");
+        assert!(matches!(
+            from_bytes::<&str>(b"03:abc"),
+            Err(Error::ExpectedInteger)
+        ));
+        assert!(from_bytes::<de::IgnoredAny>(b"03:abc").is_err());
+    }
+
+    #[test]
+    fn test_any_integer_width() {
+        struct Width;
+
+        impl<'de> Visitor<'de> for Width {
+            type Value = &'static str;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("an integer")
+            }
+
+            fn visit_i8<E>(self, _v: i8) -> Result<Self::Value, E> {
+                Ok("i8")
+            }
+
+            fn visit_i16<E>(self, _v: i16) -> Result<Self::Value, E> {
+                Ok("i16")
+            }
+
+            fn visit_i32<E>(self, _v: i32) -> Result<Self::Value, E> {
+                Ok("i32")
+            }
+
+            fn visit_i64<E>(self, _v: i64) -> Result<Self::Value, E> {
+                Ok("i64")
+            }
+
+            fn visit_u8<E>(self, _v: u8) -> Result<Self::Value, E> {
+                Ok("u8")
+            }
+
+            fn visit_u16<E>(self, _v: u16) -> Result<Self::Value, E> {
+                Ok("u16")
+            }
+
+            fn visit_u32<E>(self, _v: u32) -> Result<Self::Value, E> {
+                Ok("u32")
+            }
+
+            fn visit_u64<E>(self, _v: u64) -> Result<Self::Value, E> {
+                Ok("u64")
+            }
+        }
+
+        let width = |input: &[u8]| {
+            de::Deserializer::deserialize_any(&mut Deserializer::from_bytes(input), Width).unwrap()
+        };
+        assert_eq!(width(b"i-128e"), "i8");
+        assert_eq!(width(b"i-129e"), "i16");
+        assert_eq!(width(b"i-40000e"), "i32");
+        assert_eq!(width(b"i-9223372036854775808e"), "i64");
+        assert_eq!(width(b"i255e"), "u8");
+        assert_eq!(width(b"i256e"), "u16");
+        assert_eq!(width(b"i65536e"), "u32");
+        assert_eq!(width(b"i18446744073709551615e"), "u64");
+    }
+
+    #[test]
+    fn test_malformed_input() {
+        use serde::de::IgnoredAny;
+
+        assert!(matches!(
+            from_bytes::<IgnoredAny>(b"li1ex"),
+            Err(Error::Syntax(4))
+        ));
+        assert!(matches!(
+            from_bytes::<Vec<()>>(b"li1ee"),
+            Err(Error::Syntax(1))
+        ));
+        #[derive(Deserialize, Debug)]
+        struct Nested(#[allow(dead_code)] Vec<Nested>);
+
+        assert!(matches!(
+            from_bytes::<Nested>(&[b'l'; 100_000]),
+            Err(Error::RecursionLimitExceeded)
+        ));
+        assert!(matches!(
+            from_bytes::<IgnoredAny>(&[b'l'; 100_000]),
+            Err(Error::RecursionLimitExceeded)
+        ));
+        let nested = |depth| [vec![b'l'; depth], vec![b'e'; depth]].concat();
+        assert!(from_bytes::<IgnoredAny>(&nested(MAX_DEPTH)).is_ok());
+        assert!(matches!(
+            from_bytes::<IgnoredAny>(&nested(MAX_DEPTH + 1)),
+            Err(Error::RecursionLimitExceeded)
+        ));
+        assert!(matches!(
+            from_bytes::<char>(b"2:\xff\xfe"),
+            Err(Error::ExpectedString)
+        ));
+        assert!(from_bytes::<String>(b"2:\xff\xfe").is_err());
+        assert_eq!(from_bytes::<char>("2:é".as_bytes()).unwrap(), 'é');
+        assert_eq!(from_bytes::<String>(b"0:").unwrap(), "");
+        assert_eq!(from_bytes::<Vec<String>>(b"l0:0:e").unwrap(), vec!["", ""]);
+    }
+
+    #[test]
+    fn test_untagged_str_or_bytes() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        #[serde(untagged)]
+        enum Id<'a> {
+            Text(&'a str),
+            Binary(&'a [u8]),
+        }
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Inner<'a> {
+            #[serde(borrow)]
+            id: Id<'a>,
+        }
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Outer<'a> {
+            #[serde(borrow, flatten)]
+            inner: Inner<'a>,
+        }
+
+        let input = b"l4:spam2:\xeb\x8be";
+        let ids: Vec<Id> = from_bytes(input).unwrap();
+        assert_eq!(ids, vec![Id::Text("spam"), Id::Binary(&[0xeb, 0x8b])]);
+        assert!(matches!(ids[1], Id::Binary(bytes) if bytes.as_ptr() == input[9..].as_ptr()));
+
+        let outer: Outer = from_bytes(b"d2:id2:\xeb\x8be").unwrap();
+        assert_eq!(outer.inner.id, Id::Binary(&[0xeb, 0x8b]));
+    }
+
+    #[test]
+    fn test_skip_unknown_keys() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Info {
+            name: String,
+        }
+
+        let mut input = b"d6:lengthi-12e6:pieces60000:".to_vec();
+        input.resize(input.len() + 60000, 0xff);
+        input.extend_from_slice(b"5:filesld4:pathl1:a1:beee4:name4:spam1:v0:e");
+        assert_eq!(
+            from_bytes::<Info>(&input).unwrap(),
+            Info {
+                name: "spam".into()
+            }
+        );
+
+        let input = b"i1ed1:al1:beeli-7ee4:tail";
+        let mut deserializer = Deserializer::from_bytes(input);
+        for _ in 0..3 {
+            de::IgnoredAny::deserialize(&mut deserializer).unwrap();
+        }
+        assert_eq!(deserializer.remaining(), b"4:tail");
+
+        for malformed in [
+            &b"i12"[..],
+            b"ie",
+            b"i-e",
+            b"i1xe",
+            b"l1:a",
+            b"d3:ab",
+            b"lxe",
+            b"d1:ae",
+            b"di1ei2ee",
+            b"dl1:ae1:be",
+            b"d1:ai1e1:be",
+        ] {
+            assert!(from_bytes::<de::IgnoredAny>(malformed).is_err());
+        }
+    }
+
+    #[test]
+    fn test_borrowed_identifier() {
+        struct Borrowed;
+
+        impl<'de> Visitor<'de> for Borrowed {
+            type Value = &'de [u8];
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a borrowed identifier")
+            }
+
+            fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E> {
+                Ok(v.as_bytes())
+            }
+
+            fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<Self::Value, E> {
+                Ok(v)
+            }
+        }
+
+        for input in [&b"4:port"[..], b"2:\xff\xfe"] {
+            let mut deserializer = Deserializer::from_bytes(input);
+            let identifier =
+                de::Deserializer::deserialize_identifier(&mut deserializer, Borrowed).unwrap();
+            assert_eq!(identifier.as_ptr(), input[2..].as_ptr());
+        }
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Port {
+            port: u16,
+        }
+
+        assert_eq!(
+            from_bytes::<Port>(b"d2:\xff\xfei1e4:porti80ee").unwrap(),
+            Port { port: 80 }
+        );
+    }
+
+    #[test]
+    fn test_zero_copy() {
+        use std::borrow::Cow;
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Ping<'a> {
+            id: &'a [u8],
+            #[serde(borrow)]
+            token: Cow<'a, [u8]>,
+            #[serde(borrow)]
+            name: Cow<'a, str>,
+            v: &'a str,
+            #[serde(with = "binary_string::borrowed", borrow)]
+            target: &'a [u8],
+        }
+
+        let input = b"d2:id2:\x01\x025:token1:\xff4:name4:spam1:v2:LT6:target1:te";
+        let ping: Ping = from_bytes(input).unwrap();
+        assert_eq!(ping.id.as_ptr(), input[7..].as_ptr());
+        assert!(matches!(ping.token, Cow::Borrowed(b"\xff")));
+        assert!(matches!(ping.name, Cow::Borrowed("spam")));
+        assert_eq!(ping.v, "LT");
+        assert_eq!(ping.target, b"t");
+    }
+
+    #[test]
+    fn test_prefix() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Data {
+            msg_type: u8,
+            piece: u32,
+        }
+
+        let input = b"d8:msg_typei1e5:piecei0eeRAW PIECE DATA";
+        let (data, rest) = from_bytes_prefix::<Data>(input).unwrap();
+        assert_eq!(
+            data,
+            Data {
+                msg_type: 1,
+                piece: 0
+            }
+        );
+        assert_eq!(rest, b"RAW PIECE DATA");
+
+        let mut deserializer = Deserializer::from_bytes(input);
+        Data::deserialize(&mut deserializer).unwrap();
+        assert_eq!(deserializer.position(), 25);
+        assert_eq!(deserializer.remaining(), b"RAW PIECE DATA");
+        assert!(deserializer.end().is_err());
     }
 }
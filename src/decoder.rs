@@ -0,0 +1,232 @@
+use serde::Deserialize;
+
+use crate::de::{from_bytes, has_leading_zero, Nesting};
+use crate::error::Error;
+
+/// Result of feeding bytes into a [`Decoder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// The value is not complete yet, at least `at_least` more bytes are required.
+    NeedMore { at_least: usize },
+    /// A complete value occupies the first `len` bytes of the buffer.
+    Complete { len: usize },
+}
+
+/// Incremental decoder for bencode values arriving in arbitrary chunks.
+///
+/// Bytes are scanned once: every call to [`Decoder::feed`] resumes from the
+/// last token boundary instead of re-parsing the buffer from scratch.
+#[derive(Debug, Default)]
+pub struct Decoder {
+    buffer: Vec<u8>,
+    position: usize,
+    nesting: Nesting,
+    complete: Option<usize>,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends `bytes` to the internal buffer and continues scanning.
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<Status, Error> {
+        self.buffer.extend_from_slice(bytes);
+        self.scan()
+    }
+
+    /// Bytes received so far, including any past the end of a complete value.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// Length of the complete value, if one has been received.
+    pub fn complete_len(&self) -> Option<usize> {
+        self.complete
+    }
+
+    /// Deserializes the complete value, borrowing from the internal buffer.
+    pub fn value<'a, T>(&'a self) -> Result<T, Error>
+    where
+        T: Deserialize<'a>,
+    {
+        let len = self.complete.ok_or(Error::Eof)?;
+        from_bytes(&self.buffer[..len])
+    }
+
+    /// Removes the complete value from the buffer and returns its bytes.
+    ///
+    /// Bytes received after the value are kept, call [`Decoder::feed`] (with an
+    /// empty slice if nothing new arrived) to start scanning the next value.
+    pub fn take(&mut self) -> Option<Vec<u8>> {
+        let len = self.complete.take()?;
+        let rest = self.buffer.split_off(len);
+        self.position = 0;
+        self.nesting = Nesting::default();
+        Some(std::mem::replace(&mut self.buffer, rest))
+    }
+
+    fn scan(&mut self) -> Result<Status, Error> {
+        if let Some(len) = self.complete {
+            return Ok(Status::Complete { len });
+        }
+        loop {
+            let token = match self.buffer.get(self.position) {
+                Some(token) => *token,
+                None => return Ok(Status::NeedMore { at_least: 1 }),
+            };
+            if !self.nesting.allows(token) {
+                return Err(Error::Syntax(self.position));
+            }
+            match token {
+                b'i' => match self.scan_integer()? {
+                    Some(end) => self.position = end,
+                    None => return Ok(Status::NeedMore { at_least: 1 }),
+                },
+                b'0'..=b'9' => match self.scan_byte_string()? {
+                    Ok(end) => self.position = end,
+                    Err(at_least) => return Ok(Status::NeedMore { at_least }),
+                },
+                b'l' | b'd' => {
+                    self.nesting.open(token == b'd')?;
+                    self.position += 1;
+                }
+                b'e' => {
+                    self.nesting.close();
+                    self.position += 1;
+                }
+                _ => return Err(Error::Syntax(self.position)),
+            }
+            if matches!(token, b'i' | b'0'..=b'9') {
+                self.nesting.item();
+            }
+            if self.nesting.is_empty() {
+                self.complete = Some(self.position);
+                return Ok(Status::Complete { len: self.position });
+            }
+        }
+    }
+
+    /// Returns the end of the integer token, or `None` if it is still incomplete.
+    fn scan_integer(&self) -> Result<Option<usize>, Error> {
+        let body = &self.buffer[self.position + 1..];
+        let digits = body.strip_prefix(b"-").unwrap_or(body);
+        if has_leading_zero(digits, digits.len() < body.len()) {
+            return Err(Error::ExpectedInteger);
+        }
+        match digits.iter().position(|ch| !ch.is_ascii_digit()) {
+            Some(0) => Err(Error::ExpectedInteger),
+            Some(index) if digits[index] == b'e' => {
+                Ok(Some(self.buffer.len() - digits.len() + index + 1))
+            }
+            Some(_) => Err(Error::ExpectedInteger),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the end of the byte string, or how many bytes are still missing.
+    fn scan_byte_string(&self) -> Result<Result<usize, usize>, Error> {
+        let body = &self.buffer[self.position..];
+        if has_leading_zero(body, false) {
+            return Err(Error::ExpectedInteger);
+        }
+        let colon = match body.iter().position(|ch| !ch.is_ascii_digit()) {
+            Some(index) if body[index] == b':' => index,
+            Some(_) => return Err(Error::ExpectedInteger),
+            None => return Ok(Err(1)),
+        };
+        let mut size: usize = 0;
+        for ch in &body[..colon] {
+            size = size
+                .checked_mul(10)
+                .and_then(|size| size.checked_add(usize::from(ch - b'0')))
+                .ok_or(Error::LargeNumber)?;
+        }
+        let end = (self.position + colon + 1)
+            .checked_add(size)
+            .ok_or(Error::LargeNumber)?;
+        if end > self.buffer.len() {
+            Ok(Err(end - self.buffer.len()))
+        } else {
+            Ok(Ok(end))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::*;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Ping {
+        #[serde(with = "binary_string")]
+        id: Vec<u8>,
+        port: u16,
+    }
+
+    #[test]
+    fn test_byte_by_byte() {
+        let input = b"d2:id6:\x01\x02\x03\x04\x05\x064:porti228ee";
+        let mut decoder = Decoder::new();
+        for byte in &input[..input.len() - 1] {
+            assert!(matches!(
+                decoder.feed(&[*byte]).unwrap(),
+                Status::NeedMore { .. }
+            ));
+        }
+        assert_eq!(
+            decoder.feed(b"e").unwrap(),
+            Status::Complete { len: input.len() }
+        );
+        assert_eq!(
+            decoder.value::<Ping>().unwrap(),
+            Ping {
+                id: vec![1, 2, 3, 4, 5, 6],
+                port: 228,
+            }
+        );
+    }
+
+    #[test]
+    fn test_need_more_string() {
+        let mut decoder = Decoder::new();
+        assert_eq!(
+            decoder.feed(b"l10:abc").unwrap(),
+            Status::NeedMore { at_least: 7 }
+        );
+        assert_eq!(
+            decoder.feed(b"defghij").unwrap(),
+            Status::NeedMore { at_least: 1 }
+        );
+        assert_eq!(decoder.feed(b"e").unwrap(), Status::Complete { len: 15 });
+    }
+
+    #[test]
+    fn test_take_keeps_remainder() {
+        let mut decoder = Decoder::new();
+        assert_eq!(
+            decoder.feed(b"i-42e4:sp").unwrap(),
+            Status::Complete { len: 5 }
+        );
+        assert_eq!(decoder.value::<i64>().unwrap(), -42);
+        assert_eq!(decoder.take().unwrap(), b"i-42e");
+        assert_eq!(decoder.feed(b"am").unwrap(), Status::Complete { len: 6 });
+        assert_eq!(decoder.value::<String>().unwrap(), "spam");
+    }
+
+    #[test]
+    fn test_syntax_error() {
+        let mut decoder = Decoder::new();
+        assert!(decoder.feed(b"li1x").is_err());
+        assert!(Decoder::new().feed(b"e").is_err());
+        assert!(Decoder::new().feed(b"x").is_err());
+        assert!(Decoder::new().feed(b"i-0").is_err());
+        assert!(Decoder::new().feed(b"i03").is_err());
+        assert!(Decoder::new().feed(b"03:abc").is_err());
+        assert!(Decoder::new().feed(b"i0e").is_ok());
+        assert!(Decoder::new().feed(b"d1:ae").is_err());
+        assert!(Decoder::new().feed(b"di1ei2ee").is_err());
+        assert!(Decoder::new().feed(b"d1:ai1e1:be").is_err());
+    }
+}
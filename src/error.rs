@@ -47,11 +47,26 @@ pub enum Error {
     #[error("String is too big for a char")]
     TooBigChar,
 
-    #[error("Bad syntax")]
-    Syntax,
+    #[error("Bad syntax at offset {0}")]
+    Syntax(usize),
+
+    #[error("Recursion limit exceeded")]
+    RecursionLimitExceeded,
 
     #[error("Trailing bytes were left unparsed")]
     TrailingBytes,
+
+    #[error("Invalid pointer {0:?}")]
+    InvalidPointer(String),
+
+    #[error("Missing value at {0}")]
+    MissingKey(String),
+
+    #[error("Expected {expected} at {path}")]
+    UnexpectedType {
+        path: String,
+        expected: &'static str,
+    },
 }
 
 impl ser::Error for Error {
@@ -69,4 +84,16 @@ impl de::Error for Error {
     {
         Error::Message(msg.to_string())
     }
+
+    /// Reports integers beyond `i64::MAX` that a visitor could not hold, such
+    /// as the one of `Value`, as [`Error::LargeNumber`], the error the
+    /// integer parser gives for the same input.
+    fn invalid_value(unexp: de::Unexpected, exp: &dyn de::Expected) -> Self {
+        match unexp {
+            de::Unexpected::Unsigned(integer) if i64::try_from(integer).is_err() => {
+                Error::LargeNumber
+            }
+            _ => Error::Message(format!("invalid value: {}, expected {}", unexp, exp)),
+        }
+    }
 }
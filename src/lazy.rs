@@ -0,0 +1,124 @@
+use std::cell::OnceCell;
+
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+use crate::de::from_bytes;
+use crate::error::Error;
+use crate::raw::RawValue;
+
+/// A value that is only decoded when first accessed.
+///
+/// During the outer parse the value is skipped without allocating and its
+/// encoded bytes are borrowed from the input, like a [`RawValue`]. The first
+/// successful [`Lazy::get`] decodes them and caches the result. Serializing
+/// writes the original bytes back verbatim.
+#[derive(Clone, Debug)]
+pub struct Lazy<'a, T> {
+    raw: &'a RawValue,
+    value: OnceCell<T>,
+}
+
+impl<'a, T> Lazy<'a, T> {
+    pub fn new(raw: &'a RawValue) -> Self {
+        Lazy {
+            raw,
+            value: OnceCell::new(),
+        }
+    }
+
+    /// The encoded bytes of the value.
+    pub fn raw(&self) -> &'a RawValue {
+        self.raw
+    }
+}
+
+impl<'a, T> Lazy<'a, T>
+where
+    T: Deserialize<'a>,
+{
+    /// Decodes the value, or returns the cached one if it was already decoded.
+    pub fn get(&self) -> Result<&T, Error> {
+        if let Some(value) = self.value.get() {
+            return Ok(value);
+        }
+        let value = from_bytes(self.raw.get())?;
+        Ok(self.value.get_or_init(|| value))
+    }
+
+    pub fn into_inner(self) -> Result<T, Error> {
+        match self.value.into_inner() {
+            Some(value) => Ok(value),
+            None => from_bytes(self.raw.get()),
+        }
+    }
+}
+
+impl<T> Serialize for Lazy<'_, T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.raw.serialize(serializer)
+    }
+}
+
+impl<'de: 'a, 'a, T> Deserialize<'de> for Lazy<'a, T>
+where
+    T: Deserialize<'a>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        <&'a RawValue>::deserialize(deserializer).map(Lazy::new)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::to_bytes;
+    use serde::{Deserialize, Serialize};
+    use serde_bytes::Bytes;
+
+    #[derive(Deserialize, Serialize, Debug)]
+    struct Info<'a> {
+        name: &'a str,
+        #[serde(borrow)]
+        pieces: &'a Bytes,
+    }
+
+    #[derive(Deserialize, Serialize, Debug)]
+    struct Torrent<'a> {
+        announce: &'a str,
+        #[serde(borrow)]
+        info: Lazy<'a, Info<'a>>,
+    }
+
+    #[test]
+    fn test_decodes_on_access() {
+        let input = b"d8:announce3:url4:infod4:name4:spam6:pieces3:\x01\x02\x03ee";
+        let torrent: Torrent = from_bytes(input).unwrap();
+        assert_eq!(torrent.announce, "url");
+        assert!(torrent.info.value.get().is_none());
+        assert_eq!(torrent.info.raw().get(), &input[22..input.len() - 1]);
+
+        let info = torrent.info.get().unwrap();
+        assert_eq!(info.name, "spam");
+        assert_eq!(info.pieces.as_ref(), b"\x01\x02\x03");
+        assert!(std::ptr::eq(info, torrent.info.get().unwrap()));
+
+        assert_eq!(to_bytes(&torrent).unwrap(), input);
+        assert_eq!(torrent.info.into_inner().unwrap().name, "spam");
+    }
+
+    #[test]
+    fn test_errors_on_access() {
+        let input = b"d8:announce3:url4:infod4:name4:spamee";
+        let torrent: Torrent = from_bytes(input).unwrap();
+        assert!(torrent.info.get().is_err());
+        assert!(torrent.info.get().is_err());
+        assert!(from_bytes::<Torrent>(b"d8:announce3:url4:infod4:namee").is_err());
+    }
+}
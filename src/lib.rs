@@ -1,7 +1,23 @@
+mod byte_string;
 mod de;
+mod decoder;
 mod error;
+mod lazy;
+mod macros;
+mod raw;
 mod ser;
+mod spanned;
+pub mod tape;
 pub mod utils;
-pub use de::{from_bytes, Deserializer};
+mod value;
+pub use byte_string::ByteString;
+pub use de::{from_bytes, from_bytes_prefix, Deserializer, StreamDeserializer};
+pub use decoder::{Decoder, Status};
 pub use error::Error;
-pub use ser::Serializer;
+pub use lazy::Lazy;
+pub use raw::RawValue;
+pub use ser::{to_bytes, Serializer};
+pub use spanned::Spanned;
+pub use value::{
+    escape_key, from_value, to_value, Dict, DictView, ListView, Patch, Value, ValueRef,
+};
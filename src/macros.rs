@@ -0,0 +1,105 @@
+/// Builds a [`Value`](crate::Value) from a literal, in the spirit of
+/// `serde_json::json!`.
+///
+/// Lists are written as `[...]` and dictionaries as `{ key: value, ... }`.
+/// Keys are anything convertible into `Vec<u8>`, such as `"q"` or `b"id"`;
+/// any other value is converted with `Value::from`, so integers, strings,
+/// byte strings and existing values can be mixed freely:
+/// `bencode!({ "q": "ping", "a": { "id": b"\xeb\x8b", "ports": [6881] } })`.
+#[macro_export]
+macro_rules! bencode {
+    ([ $($elements:tt)* ]) => {
+        $crate::bencode_internal!(@list [] () ($($elements)*))
+    };
+    ({ $($entries:tt)* }) => {{
+        #[allow(unused_mut)]
+        let mut dict = $crate::Dict::new();
+        $crate::bencode_internal!(@dict dict () ($($entries)*));
+        $crate::Value::Dict(dict)
+    }};
+    ($other:expr) => {
+        $crate::Value::from($other)
+    };
+}
+
+/// Like [`bencode!`], but returns the encoded bytes of the value.
+#[macro_export]
+macro_rules! bencode_bytes {
+    ($($value:tt)+) => {
+        $crate::to_bytes(&$crate::bencode!($($value)+))
+            .expect("a Value can always be serialized")
+    };
+}
+
+/// Token munchers behind [`bencode!`]: elements and values are collected
+/// token by token until the next comma, keys until the next colon.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! bencode_internal {
+    (@list [$($done:expr,)*] () ()) => {
+        $crate::Value::List(vec![$($done,)*])
+    };
+    (@list [$($done:expr,)*] () ($element:tt $(, $($rest:tt)*)?)) => {
+        $crate::bencode_internal!(
+            @list [$($done,)* $crate::bencode!($element),] () ($($($rest)*)?)
+        )
+    };
+    (@list [$($done:expr,)*] ($($element:tt)+) ($(, $($rest:tt)*)?)) => {
+        $crate::bencode_internal!(
+            @list [$($done,)* $crate::bencode!($($element)+),] () ($($($rest)*)?)
+        )
+    };
+    (@list [$($done:expr,)*] ($($element:tt)*) ($next:tt $($rest:tt)*)) => {
+        $crate::bencode_internal!(@list [$($done,)*] ($($element)* $next) ($($rest)*))
+    };
+
+    (@dict $dict:ident () ()) => {};
+    (@dict $dict:ident () ($key:tt : $value:tt $(, $($rest:tt)*)?)) => {
+        $dict = $dict.insert($key, $crate::bencode!($value));
+        $crate::bencode_internal!(@dict $dict () ($($($rest)*)?));
+    };
+    (@dict $dict:ident ($($key:tt)+) (: $($rest:tt)*)) => {
+        $crate::bencode_internal!(@value $dict ($($key)+) () ($($rest)*));
+    };
+    (@dict $dict:ident ($($key:tt)*) ($next:tt $($rest:tt)*)) => {
+        $crate::bencode_internal!(@dict $dict ($($key)* $next) ($($rest)*));
+    };
+
+    (@value $dict:ident ($($key:tt)+) ($($value:tt)+) ($(, $($rest:tt)*)?)) => {
+        $dict = $dict.insert($($key)+, $crate::bencode!($($value)+));
+        $crate::bencode_internal!(@dict $dict () ($($($rest)*)?));
+    };
+    (@value $dict:ident ($($key:tt)+) ($($value:tt)*) ($next:tt $($rest:tt)*)) => {
+        $crate::bencode_internal!(@value $dict ($($key)+) ($($value)* $next) ($($rest)*));
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{from_bytes, Value};
+
+    #[test]
+    fn test_bencode() {
+        let id = b"\xeb\x8b".to_vec();
+        let value = bencode!({
+            "a": { "id": id.clone(), "port": 6800 + 81 },
+            b"list": [1, -2, [], [b"x", {}], "y".to_string()],
+            "n": i64::MIN,
+        });
+        assert_eq!(
+            value,
+            from_bytes::<Value>(
+                b"d1:ad2:id2:\xeb\x8b4:porti6881ee4:listli1ei-2elel1:xdee1:ye1:ni-9223372036854775808ee"
+            )
+            .unwrap()
+        );
+        assert_eq!(bencode!([]), Value::List(Vec::new()));
+        assert_eq!(bencode!(-7), Value::Integer(-7));
+    }
+
+    #[test]
+    fn test_bencode_bytes() {
+        assert_eq!(bencode_bytes!({ "b": 2, "a": [1] }), b"d1:ali1ee1:bi2ee");
+        assert_eq!(bencode_bytes!(b"spam"), b"4:spam");
+    }
+}
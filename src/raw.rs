@@ -0,0 +1,204 @@
+use std::fmt;
+
+use serde::de::{Deserialize, Deserializer, IgnoredAny, Visitor};
+use serde::ser::{Serialize, Serializer};
+use serde_bytes::Bytes;
+
+use crate::de::from_bytes;
+use crate::error::Error;
+use crate::value::DebugBytes;
+
+/// Newtype struct name recognized by this crate's serializers and
+/// deserializers to pass raw encoded bytes through untouched.
+pub(crate) const TOKEN: &str = "$bencode::private::RawValue";
+
+/// The exact encoded bytes of a single bencode value.
+///
+/// Deserializing a `&RawValue` field borrows the original input slice of the
+/// value without decoding it, and serializing writes the bytes back verbatim.
+/// This is what computing an info-hash needs: the SHA-1 of the `info`
+/// dictionary exactly as it was received, even if it is not canonical.
+/// `Box<RawValue>` owns a copy of the bytes instead.
+#[repr(transparent)]
+pub struct RawValue([u8]);
+
+impl RawValue {
+    fn from_borrowed(bytes: &[u8]) -> &Self {
+        // SAFETY: `RawValue` is a transparent wrapper around `[u8]`.
+        unsafe { &*(bytes as *const [u8] as *const RawValue) }
+    }
+
+    fn from_owned(bytes: Box<[u8]>) -> Box<Self> {
+        // SAFETY: `RawValue` is a transparent wrapper around `[u8]`.
+        unsafe { Box::from_raw(Box::into_raw(bytes) as *mut RawValue) }
+    }
+
+    /// Checks that `bytes` hold exactly one well formed value.
+    pub fn from_slice(bytes: &[u8]) -> Result<&Self, Error> {
+        from_bytes::<IgnoredAny>(bytes)?;
+        Ok(Self::from_borrowed(bytes))
+    }
+
+    /// The encoded bytes of the value.
+    pub fn get(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl ToOwned for RawValue {
+    type Owned = Box<RawValue>;
+
+    fn to_owned(&self) -> Self::Owned {
+        Self::from_owned(self.0.into())
+    }
+}
+
+impl Clone for Box<RawValue> {
+    fn clone(&self) -> Self {
+        (**self).to_owned()
+    }
+}
+
+impl PartialEq for RawValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for RawValue {}
+
+impl fmt::Debug for RawValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("RawValue")
+            .field(&DebugBytes(&self.0))
+            .finish()
+    }
+}
+
+impl Serialize for RawValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(TOKEN, Bytes::new(&self.0))
+    }
+}
+
+struct RawVisitor;
+
+impl<'de> Visitor<'de> for RawVisitor {
+    type Value = &'de RawValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a raw bencode value borrowed from the input")
+    }
+
+    fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<Self::Value, E> {
+        Ok(RawValue::from_borrowed(v))
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for &'a RawValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(TOKEN, RawVisitor)
+    }
+}
+
+struct BoxedVisitor;
+
+impl<'de> Visitor<'de> for BoxedVisitor {
+    type Value = Box<RawValue>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a raw bencode value")
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(RawValue::from_owned(v.into()))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(RawValue::from_owned(v.into_boxed_slice()))
+    }
+}
+
+impl<'de> Deserialize<'de> for Box<RawValue> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(TOKEN, BoxedVisitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{from_value, to_bytes, to_value, Value};
+    use serde::{Deserialize, Serialize};
+
+    // Keys of `info` are not sorted, so re-encoding it would change its hash.
+    const TORRENT: &[u8] = b"d8:announce3:url4:infod4:name4:spam6:lengthi3eee";
+
+    #[derive(Deserialize, Serialize, Debug)]
+    struct Torrent<'a> {
+        announce: &'a str,
+        #[serde(borrow)]
+        info: &'a RawValue,
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    struct OwnedTorrent {
+        announce: String,
+        info: Box<RawValue>,
+    }
+
+    #[test]
+    fn test_borrows_exact_bytes() {
+        let torrent: Torrent = from_bytes(TORRENT).unwrap();
+        assert_eq!(torrent.info.get(), b"d4:name4:spam6:lengthi3ee");
+        assert_eq!(
+            torrent.info.get().as_ptr(),
+            TORRENT[22..].as_ptr(),
+            "the raw value must point into the input"
+        );
+        assert_eq!(to_bytes(&torrent).unwrap(), TORRENT);
+    }
+
+    #[test]
+    fn test_owned() {
+        let torrent: OwnedTorrent = from_bytes(TORRENT).unwrap();
+        assert_eq!(torrent.info.get(), b"d4:name4:spam6:lengthi3ee");
+        assert_eq!(to_bytes(&torrent).unwrap(), TORRENT);
+        assert_eq!(torrent.info.clone(), torrent.info);
+    }
+
+    #[test]
+    fn test_value_conversions() {
+        let torrent: OwnedTorrent = from_bytes(TORRENT).unwrap();
+        let value = to_value(&torrent).unwrap();
+        assert_eq!(value, from_bytes::<Value>(TORRENT).unwrap());
+
+        // Going through `Value` sorts the keys, the bytes are re-encoded.
+        let owned: OwnedTorrent = from_value(value).unwrap();
+        assert_eq!(owned.info.get(), b"d6:lengthi3e4:name4:spame");
+    }
+
+    #[test]
+    fn test_from_slice() {
+        assert_eq!(RawValue::from_slice(b"li1ee").unwrap().get(), b"li1ee");
+        assert!(RawValue::from_slice(b"li1e").is_err());
+        assert!(RawValue::from_slice(b"i1ei2e").is_err());
+        assert!(RawValue::from_slice(b"d1:ae").is_err());
+        assert!(RawValue::from_slice(b"di1ei2ee").is_err());
+        assert!(from_bytes::<Vec<&RawValue>>(b"ld1:aee").is_err());
+        assert!(from_bytes::<Vec<Box<RawValue>>>(b"ldi1ei2eee").is_err());
+        assert_eq!(
+            format!("{:?}", RawValue::from_slice(b"3:abc").unwrap()),
+            r#"RawValue("3:abc")"#
+        );
+    }
+}
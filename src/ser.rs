@@ -1,21 +1,21 @@
 use serde::{ser, Serialize};
 
 use crate::error::Error;
+use crate::raw;
 
+#[derive(Default)]
 pub struct Serializer {
     output: Vec<u8>,
+    key_start: usize,
+    raw: bool,
 }
 
 fn digits(x: usize) -> impl Iterator<Item = u8> {
     let mut place = 10000000000000000000u64;
     std::iter::from_fn(move || {
-        if place > 0 {
-            let digit = (x as u64 / place) % 10;
-            place /= 10;
-            Some(digit as u8)
-        } else {
-            None
-        }
+        let digit = (x as u64).checked_div(place)? % 10;
+        place /= 10;
+        Some(digit as u8)
     })
 }
 
@@ -40,9 +40,23 @@ impl Serializer {
             self.output.push(digit + 48);
         }
     }
+
+    /// Removes the key written at `key_start` when the value after it wrote
+    /// nothing (`None` or `()`), so the dictionary stays well formed.
+    fn serialize_dict_value<T>(&mut self, key_start: usize, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let value_start = self.output.len();
+        value.serialize(&mut *self)?;
+        if self.output.len() == value_start {
+            self.output.truncate(key_start);
+        }
+        Ok(())
+    }
 }
 
-impl<'a> ser::Serializer for &'a mut Serializer {
+impl ser::Serializer for &mut Serializer {
     type Ok = ();
 
     type Error = Error;
@@ -65,7 +79,7 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         if v {
             self.serialize_u32(1)
         } else {
-            self.serialize_u32(1)
+            self.serialize_u32(0)
         }
     }
 
@@ -133,19 +147,17 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         Ok(())
     }
 
-    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+    fn serialize_f32(self, _v: f32) -> Result<Self::Ok, Self::Error> {
         Err(Error::InvalidValue("Cannot serialize f32"))
     }
 
-    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+    fn serialize_f64(self, _v: f64) -> Result<Self::Ok, Self::Error> {
         Err(Error::InvalidValue("Cannot serialize f64"))
     }
 
     fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
         let mut buf = [0u8; 4];
-        self.serialize_bytes(v.encode_utf8(&mut buf).as_bytes());
-
-        Ok(())
+        self.serialize_bytes(v.encode_utf8(&mut buf).as_bytes())
     }
 
     fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
@@ -153,6 +165,10 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     }
 
     fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        if self.raw {
+            self.output.extend_from_slice(v);
+            return Ok(());
+        }
         self.push_length(v.len());
         self.output.push(b':');
         self.output.extend_from_slice(v);
@@ -164,9 +180,9 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         Ok(())
     }
 
-    fn serialize_some<T: ?Sized + ser::Serialize>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         value.serialize(self)
     }
@@ -175,48 +191,55 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         Ok(())
     }
 
-    fn serialize_unit_struct(self, name: &'static str) -> Result<Self::Ok, Self::Error> {
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
         Ok(())
     }
 
     fn serialize_unit_variant(
         self,
-        name: &'static str,
-        variant_index: u32,
+        _name: &'static str,
+        _variant_index: u32,
         variant: &'static str,
     ) -> Result<Self::Ok, Self::Error> {
-        self.serialize_str(name)
+        self.serialize_str(variant)
     }
 
-    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+    fn serialize_newtype_struct<T>(
         self,
         name: &'static str,
         value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
+        if name == raw::TOKEN {
+            self.raw = true;
+            let result = value.serialize(&mut *self);
+            self.raw = false;
+            return result;
+        }
         value.serialize(self)
     }
 
-    fn serialize_newtype_variant<T: ?Sized + ser::Serialize>(
+    fn serialize_newtype_variant<T>(
         self,
-        name: &'static str,
-        variant_index: u32,
+        _name: &'static str,
+        _variant_index: u32,
         variant: &'static str,
         value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         self.output.push(b'd');
-        self.serialize_str(name)?;
-        value.serialize(&mut *self)?;
+        let key_start = self.output.len();
+        self.serialize_str(variant)?;
+        self.serialize_dict_value(key_start, value)?;
         self.output.push(b'e');
         Ok(())
     }
 
-    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
         self.output.push(b'l');
         Ok(self)
     }
@@ -227,7 +250,7 @@ impl<'a> ser::Serializer for &'a mut Serializer {
 
     fn serialize_tuple_struct(
         self,
-        name: &'static str,
+        _name: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleStruct, Self::Error> {
         self.serialize_seq(Some(len))
@@ -235,25 +258,25 @@ impl<'a> ser::Serializer for &'a mut Serializer {
 
     fn serialize_tuple_variant(
         self,
-        name: &'static str,
-        variant_index: u32,
+        _name: &'static str,
+        _variant_index: u32,
         variant: &'static str,
-        len: usize,
+        _len: usize,
     ) -> Result<Self::SerializeTupleVariant, Self::Error> {
         self.output.push(b'd');
-        self.serialize_str(variant);
+        self.serialize_str(variant)?;
         self.output.push(b'l');
         Ok(self)
     }
 
-    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
         self.output.push(b'd');
         Ok(self)
     }
 
     fn serialize_struct(
         self,
-        name: &'static str,
+        _name: &'static str,
         len: usize,
     ) -> Result<Self::SerializeStruct, Self::Error> {
         self.serialize_map(Some(len))
@@ -261,10 +284,10 @@ impl<'a> ser::Serializer for &'a mut Serializer {
 
     fn serialize_struct_variant(
         self,
-        name: &'static str,
-        variant_index: u32,
+        _name: &'static str,
+        _variant_index: u32,
         variant: &'static str,
-        len: usize,
+        _len: usize,
     ) -> Result<Self::SerializeStructVariant, Self::Error> {
         self.output.push(b'd');
         variant.serialize(&mut *self)?;
@@ -273,14 +296,14 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     }
 }
 
-impl<'a> ser::SerializeSeq for &'a mut Serializer {
+impl ser::SerializeSeq for &mut Serializer {
     type Ok = ();
 
     type Error = Error;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         value.serialize(&mut **self)
     }
@@ -291,14 +314,14 @@ impl<'a> ser::SerializeSeq for &'a mut Serializer {
     }
 }
 
-impl<'a> ser::SerializeTuple for &'a mut Serializer {
+impl ser::SerializeTuple for &mut Serializer {
     type Ok = ();
 
     type Error = Error;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         value.serialize(&mut **self)
     }
@@ -309,14 +332,14 @@ impl<'a> ser::SerializeTuple for &'a mut Serializer {
     }
 }
 
-impl<'a> ser::SerializeTupleStruct for &'a mut Serializer {
+impl ser::SerializeTupleStruct for &mut Serializer {
     type Ok = ();
 
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         value.serialize(&mut **self)
     }
@@ -327,14 +350,14 @@ impl<'a> ser::SerializeTupleStruct for &'a mut Serializer {
     }
 }
 
-impl<'a> ser::SerializeTupleVariant for &'a mut Serializer {
+impl ser::SerializeTupleVariant for &mut Serializer {
     type Ok = ();
 
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         value.serialize(&mut **self)
     }
@@ -346,15 +369,16 @@ impl<'a> ser::SerializeTupleVariant for &'a mut Serializer {
     }
 }
 
-impl<'a> ser::SerializeMap for &'a mut Serializer {
+impl ser::SerializeMap for &mut Serializer {
     type Ok = ();
 
     type Error = Error;
 
-    fn serialize_key<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_key<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
+        self.key_start = self.output.len();
         value.serialize(&mut **self)
     }
 
@@ -363,29 +387,26 @@ impl<'a> ser::SerializeMap for &'a mut Serializer {
         Ok(())
     }
 
-    fn serialize_value<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        self.serialize_dict_value(self.key_start, value)
     }
 }
 
-impl<'a> ser::SerializeStruct for &'a mut Serializer {
+impl ser::SerializeStruct for &mut Serializer {
     type Ok = ();
 
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(
-        &mut self,
-        key: &'static str,
-        value: &T,
-    ) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
+        let key_start = self.output.len();
         key.serialize(&mut **self)?;
-        value.serialize(&mut **self)
+        self.serialize_dict_value(key_start, value)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
@@ -394,21 +415,18 @@ impl<'a> ser::SerializeStruct for &'a mut Serializer {
     }
 }
 
-impl<'a> ser::SerializeStructVariant for &'a mut Serializer {
+impl ser::SerializeStructVariant for &mut Serializer {
     type Ok = ();
 
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(
-        &mut self,
-        key: &'static str,
-        value: &T,
-    ) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
+        let key_start = self.output.len();
         key.serialize(&mut **self)?;
-        value.serialize(&mut **self)
+        self.serialize_dict_value(key_start, value)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
@@ -422,7 +440,7 @@ pub fn to_bytes<T>(value: &T) -> Result<Vec<u8>, Error>
 where
     T: Serialize,
 {
-    let mut serializer = Serializer { output: Vec::new() };
+    let mut serializer = Serializer::default();
     value.serialize(&mut serializer)?;
 
     Ok(serializer.output)
@@ -452,12 +470,83 @@ mod test {
             implied_port: false,
         };
 
-        let mut serializer = Serializer { output: Vec::new() };
+        let mut serializer = Serializer::default();
         packet.serialize(&mut serializer).unwrap();
 
-        println!("{:?}", unsafe {
-            String::from_utf8_unchecked(serializer.output)
-        });
+        assert_eq!(
+            serializer.output,
+            b"d2:id6:\x01\x02\x03\x04\x05\x069:info_hash9:info hash4:porti228e12:implied_porti0ee"
+        );
+    }
+
+    #[test]
+    fn test_borrowed() {
+        use std::borrow::Cow;
+
+        #[derive(Serialize, Debug)]
+        pub struct Ping<'a> {
+            #[serde(with = "binary_string::borrowed")]
+            id: &'a [u8],
+            #[serde(with = "binary_string::borrowed")]
+            token: Cow<'a, [u8]>,
+        }
+
+        let packet = Ping {
+            id: b"\x01\x02",
+            token: Cow::Owned(vec![0xff]),
+        };
+        assert_eq!(to_bytes(&packet).unwrap(), b"d2:id2:\x01\x025:token1:\xffe");
+    }
+
+    #[test]
+    fn test_skips_empty_values() {
+        use std::collections::BTreeMap;
+
+        #[derive(Serialize)]
+        pub struct Response {
+            token: Option<String>,
+            nodes: Vec<Option<u8>>,
+            values: BTreeMap<&'static str, Option<u8>>,
+            unit: (),
+        }
+
+        let packet = Response {
+            token: None,
+            nodes: vec![Some(1), None, Some(2)],
+            values: BTreeMap::from([("a", None), ("b", Some(3))]),
+            unit: (),
+        };
+        assert_eq!(
+            to_bytes(&packet).unwrap(),
+            b"d5:nodesli1ei2ee6:valuesd1:bi3eee"
+        );
+    }
+
+    #[test]
+    fn test_none_drops_key() {
+        #[derive(Serialize)]
+        pub struct Reply {
+            id: u8,
+            token: Option<String>,
+            values: Option<u8>,
+        }
+
+        #[derive(Serialize)]
+        pub enum Kind {
+            Optional(Option<u8>),
+        }
+
+        let reply = Reply {
+            id: 1,
+            token: None,
+            values: Some(2),
+        };
+        assert_eq!(to_bytes(&reply).unwrap(), b"d2:idi1e6:valuesi2ee");
+        assert_eq!(to_bytes(&Kind::Optional(None)).unwrap(), b"de");
+        assert_eq!(
+            to_bytes(&Kind::Optional(Some(3))).unwrap(),
+            b"d8:Optionali3ee"
+        );
     }
 
     #[test]
@@ -473,6 +562,7 @@ mod test {
         #[derive(Serialize, Debug)]
         pub enum Query {
             Request(AnnouncePeer),
+            #[allow(dead_code)]
             Response(String),
         }
 
@@ -483,11 +573,12 @@ mod test {
             implied_port: false,
         });
 
-        let mut serializer = Serializer { output: Vec::new() };
+        let mut serializer = Serializer::default();
         packet.serialize(&mut serializer).unwrap();
 
-        println!("{:?}", unsafe {
-            String::from_utf8_unchecked(serializer.output)
-        });
+        assert_eq!(
+            serializer.output,
+            b"d7:Requestd2:id9:picked id9:info_hash9:info hash4:porti228e12:implied_porti0eee"
+        );
     }
 }
@@ -0,0 +1,212 @@
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Range;
+
+use serde::de::value::{BorrowedStrDeserializer, UsizeDeserializer};
+use serde::de::{self, Deserialize, DeserializeSeed, Deserializer, IntoDeserializer, MapAccess};
+use serde::ser::{Serialize, Serializer};
+
+use crate::error::Error;
+
+/// Struct name and fields recognized by [`crate::Deserializer`] to report the
+/// position of a value alongside the value itself.
+pub(crate) const NAME: &str = "$bencode::private::Spanned";
+const START: &str = "$bencode::private::Spanned::start";
+const END: &str = "$bencode::private::Spanned::end";
+const VALUE: &str = "$bencode::private::Spanned::value";
+const FIELDS: &[&str] = &[START, END, VALUE];
+
+/// A value together with the byte range it was decoded from.
+///
+/// Offsets are relative to the start of the [`crate::Deserializer`] input.
+/// Only this crate's byte deserializer knows about positions, so decoding a
+/// `Spanned<T>` from a [`crate::Value`] fails. Serializing writes the inner
+/// value only.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Spanned<T> {
+    start: usize,
+    end: usize,
+    value: T,
+}
+
+impl<T> Spanned<T> {
+    /// Byte offset of the first byte of the value.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Byte offset just past the last byte of the value.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn span(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.value
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Serialize for Spanned<T>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.value.serialize(serializer)
+    }
+}
+
+struct SpannedVisitor<T>(PhantomData<T>);
+
+impl<'de, T> de::Visitor<'de> for SpannedVisitor<T>
+where
+    T: Deserialize<'de>,
+{
+    type Value = Spanned<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a spanned value")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let start = field(&mut map, START)?;
+        let value = field(&mut map, VALUE)?;
+        let end = field(&mut map, END)?;
+        Ok(Spanned { start, end, value })
+    }
+}
+
+/// Reads the entry `name`, which [`SpannedAccess`] always provides next.
+fn field<'de, A, T>(map: &mut A, name: &'static str) -> Result<T, A::Error>
+where
+    A: MapAccess<'de>,
+    T: Deserialize<'de>,
+{
+    match map.next_key::<&str>()? {
+        Some(key) if key == name => map.next_value(),
+        _ => Err(de::Error::custom(
+            "Spanned can only be deserialized by bencode::Deserializer",
+        )),
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Spanned<T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_struct(NAME, FIELDS, SpannedVisitor(PhantomData))
+    }
+}
+
+/// Feeds the start offset, the value and the end offset of the next value in
+/// `de` to a [`Spanned`] visitor.
+pub(crate) struct SpannedAccess<'a, 'de> {
+    de: &'a mut crate::Deserializer<'de>,
+    field: usize,
+}
+
+impl<'a, 'de> SpannedAccess<'a, 'de> {
+    pub(crate) fn new(de: &'a mut crate::Deserializer<'de>) -> Self {
+        SpannedAccess { de, field: 0 }
+    }
+}
+
+impl<'de> MapAccess<'de> for SpannedAccess<'_, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        let key = match self.field {
+            0 => START,
+            1 => VALUE,
+            2 => END,
+            _ => return Ok(None),
+        };
+        seed.deserialize(BorrowedStrDeserializer::new(key))
+            .map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        self.field += 1;
+        match self.field {
+            2 => seed.deserialize(&mut *self.de),
+            _ => {
+                let position: UsizeDeserializer<Error> = self.de.position().into_deserializer();
+                seed.deserialize(position)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{from_bytes, to_bytes};
+    use serde::Deserialize;
+    use std::collections::BTreeMap;
+
+    #[derive(Deserialize, Debug)]
+    struct File {
+        length: Spanned<u64>,
+        path: Vec<Spanned<String>>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Info {
+        files: Spanned<Vec<File>>,
+        name: Spanned<String>,
+    }
+
+    #[test]
+    fn test_spans() {
+        let input = b"d5:filesld6:lengthi42e4:pathl1:a2:bceee4:name4:spame";
+        let info: Info = from_bytes(input).unwrap();
+        let slice = |spanned: Range<usize>| &input[spanned];
+
+        assert_eq!(slice(info.files.span()), b"ld6:lengthi42e4:pathl1:a2:bceee");
+        assert_eq!(slice(info.name.span()), b"4:spam");
+        let file = &info.files.get_ref()[0];
+        assert_eq!(*file.length.get_ref(), 42);
+        assert_eq!(slice(file.length.span()), b"i42e");
+        assert_eq!(slice(file.path[1].span()), b"2:bc");
+        assert_eq!(file.path[1].start(), 32);
+        assert_eq!(file.path[1].end(), 36);
+    }
+
+    #[test]
+    fn test_map_values_and_serialize() {
+        let input = b"d1:ai1e1:bli2eee";
+        let map: BTreeMap<String, Spanned<crate::Value>> = from_bytes(input).unwrap();
+        assert_eq!(map["a"].span(), 4..7);
+        assert_eq!(map["b"].span(), 10..15);
+        assert_eq!(to_bytes(&map).unwrap(), input);
+
+        let value: crate::Value = from_bytes(input).unwrap();
+        assert!(crate::from_value::<BTreeMap<String, Spanned<i64>>>(value).is_err());
+    }
+}
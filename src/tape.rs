@@ -0,0 +1,382 @@
+//! One-pass structural index over encoded bencode for random access.
+//!
+//! [`Tape::parse`] validates the input and records every token in a flat
+//! array, in document order, together with its byte range, the index of its
+//! parent and the index of the token following it. Navigating the document
+//! then only walks that array, e.g. `tape.root().get(b"info")?.get(b"files")?.iter()`: nothing is
+//! decoded or allocated until asked for.
+
+use std::ops::Range;
+
+use serde::Deserialize;
+
+use crate::de::{from_bytes, Deserializer, Integer};
+use crate::error::Error;
+
+#[derive(Clone, Copy, Debug)]
+enum Kind<'a> {
+    Integer(Integer),
+    Bytes(&'a [u8]),
+    List,
+    Dict,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Token<'a> {
+    kind: Kind<'a>,
+    start: usize,
+    end: usize,
+    /// Index of the first token after this value and all of its children.
+    next: usize,
+    /// Index of the enclosing list or dictionary, `None` for the root.
+    parent: Option<usize>,
+    /// Number of list elements or dictionary entries, 0 for other values.
+    len: usize,
+}
+
+/// Structural index of a single encoded value.
+#[derive(Clone, Debug)]
+pub struct Tape<'a> {
+    input: &'a [u8],
+    tokens: Vec<Token<'a>>,
+}
+
+impl<'a> Tape<'a> {
+    /// Indexes `input`, which must hold exactly one well formed value.
+    pub fn parse(input: &'a [u8]) -> Result<Self, Error> {
+        let mut de = Deserializer::from_bytes(input);
+        let mut tokens = Vec::new();
+        // Open containers: token index and number of children so far.
+        let mut open: Vec<(usize, usize)> = Vec::new();
+        loop {
+            let start = de.position();
+            let byte = de.peek_byte()?;
+            if byte == b'e' {
+                let (index, children) = open.pop().ok_or(Error::Syntax(start))?;
+                let next = tokens.len();
+                let token: &mut Token = &mut tokens[index];
+                if matches!(token.kind, Kind::Dict) && children % 2 == 1 {
+                    return Err(Error::Syntax(start));
+                }
+                de.next_byte()?;
+                token.end = de.position();
+                token.next = next;
+                token.len = match token.kind {
+                    Kind::Dict => children / 2,
+                    _ => children,
+                };
+            } else {
+                let parent = open.last().map(|&(index, _)| index);
+                if let Some((index, children)) = open.last_mut() {
+                    let is_key = matches!(tokens[*index].kind, Kind::Dict) && *children % 2 == 0;
+                    if is_key && !byte.is_ascii_digit() {
+                        return Err(Error::Syntax(start));
+                    }
+                    *children += 1;
+                }
+                let kind = match byte {
+                    b'i' => Kind::Integer(de.parse_integer()?),
+                    b'0'..=b'9' => Kind::Bytes(de.parse_byte_string_borrowed()?),
+                    b'l' | b'd' => {
+                        de.next_byte()?;
+                        open.push((tokens.len(), 0));
+                        if byte == b'l' {
+                            Kind::List
+                        } else {
+                            Kind::Dict
+                        }
+                    }
+                    _ => return Err(Error::Syntax(start)),
+                };
+                tokens.push(Token {
+                    kind,
+                    start,
+                    end: de.position(),
+                    next: tokens.len() + 1,
+                    parent,
+                    len: 0,
+                });
+            }
+            if open.is_empty() {
+                de.end()?;
+                return Ok(Tape { input, tokens });
+            }
+        }
+    }
+
+    pub fn root(&self) -> Node<'_, 'a> {
+        Node {
+            tape: self,
+            index: 0,
+        }
+    }
+
+    /// Number of indexed tokens, one per value.
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+}
+
+/// A value inside a [`Tape`].
+#[derive(Clone, Copy, Debug)]
+pub struct Node<'t, 'a> {
+    tape: &'t Tape<'a>,
+    index: usize,
+}
+
+impl<'t, 'a> Node<'t, 'a> {
+    fn token(&self) -> &'t Token<'a> {
+        &self.tape.tokens[self.index]
+    }
+
+    /// Byte range of the encoded value within the input.
+    pub fn span(&self) -> Range<usize> {
+        self.token().start..self.token().end
+    }
+
+    /// The encoded bytes of the value.
+    pub fn raw(&self) -> &'a [u8] {
+        &self.tape.input[self.span()]
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self.token().kind {
+            Kind::Integer(Integer::Negative(integer)) => Some(integer),
+            Kind::Integer(Integer::Positive(integer)) => i64::try_from(integer).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match self.token().kind {
+            Kind::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// Returns the byte string if it is valid UTF-8.
+    pub fn as_str(&self) -> Option<&'a str> {
+        std::str::from_utf8(self.as_bytes()?).ok()
+    }
+
+    pub fn is_list(&self) -> bool {
+        matches!(self.token().kind, Kind::List)
+    }
+
+    pub fn is_dict(&self) -> bool {
+        matches!(self.token().kind, Kind::Dict)
+    }
+
+    /// The list or dictionary containing this value, `None` for the root.
+    pub fn parent(&self) -> Option<Node<'t, 'a>> {
+        Some(Node {
+            tape: self.tape,
+            index: self.token().parent?,
+        })
+    }
+
+    /// Looks up `key` in a dictionary. This scans the entries in order, so it
+    /// is linear in the size of the dictionary.
+    pub fn get<K>(&self, key: K) -> Option<Node<'t, 'a>>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.entries()
+            .find(|(candidate, _)| *candidate == key.as_ref())
+            .map(|(_, value)| value)
+    }
+
+    /// Iterates over the elements of a list, or nothing for other values.
+    pub fn iter(&self) -> Iter<'t, 'a> {
+        Iter {
+            children: if self.is_list() {
+                self.children()
+            } else {
+                self.empty()
+            },
+        }
+    }
+
+    /// Iterates over the entries of a dictionary, or nothing for other values.
+    pub fn entries(&self) -> Entries<'t, 'a> {
+        Entries {
+            children: if self.is_dict() {
+                self.children()
+            } else {
+                self.empty()
+            },
+        }
+    }
+
+    /// Number of list elements or dictionary entries, 0 for other values.
+    pub fn len(&self) -> usize {
+        self.token().len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Fully decodes the value.
+    pub fn deserialize<T>(&self) -> Result<T, Error>
+    where
+        T: Deserialize<'a>,
+    {
+        from_bytes(self.raw())
+    }
+
+    fn children(&self) -> Children<'t, 'a> {
+        Children {
+            tape: self.tape,
+            index: self.index + 1,
+            end: self.token().next,
+        }
+    }
+
+    fn empty(&self) -> Children<'t, 'a> {
+        Children {
+            tape: self.tape,
+            index: 0,
+            end: 0,
+        }
+    }
+}
+
+/// Direct children of a container, skipping over their own children.
+#[derive(Clone, Debug)]
+struct Children<'t, 'a> {
+    tape: &'t Tape<'a>,
+    index: usize,
+    end: usize,
+}
+
+impl<'t, 'a> Iterator for Children<'t, 'a> {
+    type Item = Node<'t, 'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.end {
+            return None;
+        }
+        let node = Node {
+            tape: self.tape,
+            index: self.index,
+        };
+        self.index = node.token().next;
+        Some(node)
+    }
+}
+
+/// Iterator over the elements of a list, see [`Node::iter`].
+#[derive(Clone, Debug)]
+pub struct Iter<'t, 'a> {
+    children: Children<'t, 'a>,
+}
+
+impl<'t, 'a> Iterator for Iter<'t, 'a> {
+    type Item = Node<'t, 'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.children.next()
+    }
+}
+
+/// Iterator over the entries of a dictionary, see [`Node::entries`].
+#[derive(Clone, Debug)]
+pub struct Entries<'t, 'a> {
+    children: Children<'t, 'a>,
+}
+
+impl<'t, 'a> Iterator for Entries<'t, 'a> {
+    type Item = (&'a [u8], Node<'t, 'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let key = self.children.next()?.as_bytes()?;
+        let value = self.children.next()?;
+        Some((key, value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Value;
+
+    const TORRENT: &[u8] = b"d8:announce3:url4:infod5:filesld6:lengthi1e4:pathl1:aeed6:lengthi2e4:pathl1:b1:ceee4:name4:spamee";
+
+    #[test]
+    fn test_navigation() {
+        let tape = Tape::parse(TORRENT).unwrap();
+        let root = tape.root();
+        assert!(root.is_dict());
+        assert_eq!(root.len(), 2);
+        assert_eq!(root.get(b"announce").unwrap().as_str(), Some("url"));
+
+        let info = root.get(b"info").unwrap();
+        assert_eq!(info.get(b"name").unwrap().as_bytes(), Some(&b"spam"[..]));
+        let files = info.get(b"files").unwrap();
+        assert!(files.is_list());
+        let lengths: Vec<_> = files
+            .iter()
+            .map(|file| file.get(b"length").unwrap().as_int().unwrap())
+            .collect();
+        assert_eq!(lengths, [1, 2]);
+
+        let path = files.iter().nth(1).unwrap().get(b"path").unwrap();
+        assert_eq!(path.raw(), b"l1:b1:ce");
+        assert_eq!(&TORRENT[path.span()], b"l1:b1:ce");
+        assert_eq!(path.deserialize::<Vec<&str>>().unwrap(), ["b", "c"]);
+
+        let keys: Vec<_> = info.entries().map(|(key, _)| key).collect();
+        assert_eq!(keys, [&b"files"[..], b"name"]);
+        assert!(root.get(b"missing").is_none());
+        assert!(files.get(b"length").is_none());
+        assert_eq!(info.iter().count(), 0);
+        assert_eq!(files.len(), 2);
+        assert_eq!(info.len(), 2);
+
+        assert!(root.parent().is_none());
+        assert_eq!(info.parent().unwrap().span(), root.span());
+        let file = path.parent().unwrap();
+        assert_eq!(file.get(b"length").unwrap().as_int(), Some(2));
+        assert_eq!(file.parent().unwrap().span(), files.span());
+        assert_eq!(files.parent().unwrap().span(), info.span());
+        assert_eq!(
+            info.deserialize::<Value>().unwrap(),
+            crate::from_bytes::<Value>(&TORRENT[22..TORRENT.len() - 1]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_scalars() {
+        let tape = Tape::parse(b"li-1ei18446744073709551615e0:e").unwrap();
+        assert_eq!(tape.len(), 4);
+        let elements: Vec<_> = tape.root().iter().collect();
+        assert_eq!(elements[0].as_int(), Some(-1));
+        assert_eq!(elements[1].as_int(), None);
+        assert_eq!(elements[2].as_bytes(), Some(&b""[..]));
+        assert!(elements[2].is_empty());
+        assert_eq!(Tape::parse(b"i7e").unwrap().root().as_int(), Some(7));
+    }
+
+    #[test]
+    fn test_malformed_input() {
+        for input in [
+            &b""[..],
+            b"e",
+            b"l",
+            b"li1e",
+            b"di1ei2ee",
+            b"d1:ae",
+            b"i1ei2e",
+            b"le1:a",
+            b"lxe",
+            b"5:abc",
+        ] {
+            assert!(Tape::parse(input).is_err(), "{:?}", input);
+        }
+    }
+}
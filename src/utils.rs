@@ -1,18 +1,480 @@
+pub mod compact;
+
+/// Encodes byte string fields as bencode strings instead of lists of
+/// integers, use as `#[serde(with = "binary_string")]`.
+///
+/// Works for `Vec<u8>`, `Box<[u8]>`, `&[u8]` and, with the `bytes` feature,
+/// `bytes::Bytes`, as well as for `Option`s and `Vec`s of those and for maps
+/// keyed by them. Map values keep their own `Serialize` implementation.
+/// Optional fields also need `#[serde(default)]` to accept a missing key.
 pub mod binary_string {
-    use serde::{self, Deserialize, Deserializer, Serializer};
+    use std::collections::{BTreeMap, HashMap};
+    use std::hash::Hash;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
     use serde_bytes::ByteBuf;
 
-    pub fn serialize<S>(data: &Vec<u8>, serializer: S) -> Result<S::Ok, S::Error>
+    pub fn serialize<T, S>(data: &T, serializer: S) -> Result<S::Ok, S::Error>
     where
+        T: ?Sized + BinarySerialize,
         S: Serializer,
     {
-        serializer.serialize_bytes(data.as_slice())
+        data.serialize_binary(serializer)
     }
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
     where
         D: Deserializer<'de>,
+        T: BinaryDeserialize<'de>,
+    {
+        T::deserialize_binary(deserializer)
+    }
+
+    /// Types serializable by [`serialize`].
+    pub trait BinarySerialize {
+        fn serialize_binary<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer;
+    }
+
+    /// Types deserializable by [`deserialize`].
+    pub trait BinaryDeserialize<'de>: Sized {
+        fn deserialize_binary<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>;
+    }
+
+    /// Adapts a [`BinarySerialize`] reference to `Serialize`.
+    struct SerializeAs<'a, T: ?Sized>(&'a T);
+
+    impl<T> Serialize for SerializeAs<'_, T>
+    where
+        T: ?Sized + BinarySerialize,
+    {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            self.0.serialize_binary(serializer)
+        }
+    }
+
+    /// Adapts a [`BinaryDeserialize`] type to `Deserialize`.
+    #[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+    struct DeserializeAs<T>(T);
+
+    impl<'de, T> Deserialize<'de> for DeserializeAs<T>
+    where
+        T: BinaryDeserialize<'de>,
+    {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            T::deserialize_binary(deserializer).map(DeserializeAs)
+        }
+    }
+
+    impl BinarySerialize for [u8] {
+        fn serialize_binary<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            serializer.serialize_bytes(self)
+        }
+    }
+
+    impl BinarySerialize for Vec<u8> {
+        fn serialize_binary<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            serializer.serialize_bytes(self)
+        }
+    }
+
+    impl<'de> BinaryDeserialize<'de> for Vec<u8> {
+        fn deserialize_binary<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            ByteBuf::deserialize(deserializer).map(ByteBuf::into_vec)
+        }
+    }
+
+    impl BinarySerialize for Box<[u8]> {
+        fn serialize_binary<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            serializer.serialize_bytes(self)
+        }
+    }
+
+    impl<'de> BinaryDeserialize<'de> for Box<[u8]> {
+        fn deserialize_binary<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            Vec::deserialize_binary(deserializer).map(Vec::into_boxed_slice)
+        }
+    }
+
+    impl BinarySerialize for &[u8] {
+        fn serialize_binary<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            serializer.serialize_bytes(self)
+        }
+    }
+
+    impl<'de: 'a, 'a> BinaryDeserialize<'de> for &'a [u8] {
+        fn deserialize_binary<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            <&'de [u8]>::deserialize(deserializer)
+        }
+    }
+
+    #[cfg(feature = "bytes")]
+    impl BinarySerialize for bytes::Bytes {
+        fn serialize_binary<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            serializer.serialize_bytes(self)
+        }
+    }
+
+    #[cfg(feature = "bytes")]
+    impl<'de> BinaryDeserialize<'de> for bytes::Bytes {
+        fn deserialize_binary<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            Vec::deserialize_binary(deserializer).map(bytes::Bytes::from)
+        }
+    }
+
+    impl<T> BinarySerialize for Option<T>
+    where
+        T: BinarySerialize,
     {
-        let s: Vec<u8> = ByteBuf::deserialize(deserializer)?.to_vec();
-        Ok(s)
+        fn serialize_binary<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match self {
+                Some(data) => serializer.serialize_some(&SerializeAs(data)),
+                None => serializer.serialize_none(),
+            }
+        }
+    }
+
+    impl<'de, T> BinaryDeserialize<'de> for Option<T>
+    where
+        T: BinaryDeserialize<'de>,
+    {
+        fn deserialize_binary<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let data = Option::<DeserializeAs<T>>::deserialize(deserializer)?;
+            Ok(data.map(|DeserializeAs(data)| data))
+        }
+    }
+
+    impl<T> BinarySerialize for Vec<T>
+    where
+        T: BinarySerialize,
+    {
+        fn serialize_binary<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            serializer.collect_seq(self.iter().map(SerializeAs))
+        }
+    }
+
+    impl<'de, T> BinaryDeserialize<'de> for Vec<T>
+    where
+        T: BinaryDeserialize<'de>,
+    {
+        fn deserialize_binary<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let data = Vec::<DeserializeAs<T>>::deserialize(deserializer)?;
+            Ok(data.into_iter().map(|DeserializeAs(data)| data).collect())
+        }
+    }
+
+    impl<K, V> BinarySerialize for BTreeMap<K, V>
+    where
+        K: BinarySerialize,
+        V: Serialize,
+    {
+        fn serialize_binary<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            serializer.collect_map(self.iter().map(|(key, value)| (SerializeAs(key), value)))
+        }
+    }
+
+    impl<'de, K, V> BinaryDeserialize<'de> for BTreeMap<K, V>
+    where
+        K: BinaryDeserialize<'de> + Ord,
+        V: Deserialize<'de>,
+    {
+        fn deserialize_binary<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let data = BTreeMap::<DeserializeAs<K>, V>::deserialize(deserializer)?;
+            Ok(data
+                .into_iter()
+                .map(|(DeserializeAs(key), value)| (key, value))
+                .collect())
+        }
+    }
+
+    /// Entries are written in sorted key order, which for byte strings is the
+    /// canonical bencode order, rather than in the map's hash order.
+    impl<K, V> BinarySerialize for HashMap<K, V>
+    where
+        K: BinarySerialize + Ord,
+        V: Serialize,
+    {
+        fn serialize_binary<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            let sorted: BTreeMap<&K, &V> = self.iter().collect();
+            serializer.collect_map(
+                sorted
+                    .into_iter()
+                    .map(|(key, value)| (SerializeAs(key), value)),
+            )
+        }
+    }
+
+    impl<'de, K, V> BinaryDeserialize<'de> for HashMap<K, V>
+    where
+        K: BinaryDeserialize<'de> + Eq + Hash,
+        V: Deserialize<'de>,
+    {
+        fn deserialize_binary<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let data = HashMap::<DeserializeAs<K>, V>::deserialize(deserializer)?;
+            Ok(data
+                .into_iter()
+                .map(|(DeserializeAs(key), value)| (key, value))
+                .collect())
+        }
+    }
+
+    /// Zero-copy variant for `&[u8]` and `Cow<[u8]>` fields borrowing from the
+    /// input, use together with `#[serde(borrow)]`.
+    pub mod borrowed {
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<T, S>(data: &T, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            T: ?Sized + AsRef<[u8]>,
+            S: Serializer,
+        {
+            serializer.serialize_bytes(data.as_ref())
+        }
+
+        pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+        where
+            D: Deserializer<'de>,
+            T: From<&'de [u8]>,
+        {
+            <&'de [u8]>::deserialize(deserializer).map(T::from)
+        }
+    }
+}
+
+/// Encodes `[u8; N]`, such as node ids and info-hashes, as an N-byte string
+/// instead of a list of integers. Decoding a string of any other length fails
+/// with an `invalid_length` error naming both lengths.
+pub mod binary_array {
+    use std::fmt;
+
+    use serde::de::{Error, SeqAccess, Visitor};
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S, const N: usize>(data: &[u8; N], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(data)
+    }
+
+    pub fn deserialize<'de, D, const N: usize>(deserializer: D) -> Result<[u8; N], D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_bytes(ArrayVisitor::<N>)
+    }
+
+    struct ArrayVisitor<const N: usize>;
+
+    impl<'de, const N: usize> Visitor<'de> for ArrayVisitor<N> {
+        type Value = [u8; N];
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "a byte string of length {}", N)
+        }
+
+        fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+        where
+            E: Error,
+        {
+            v.try_into().map_err(|_| E::invalid_length(v.len(), &self))
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: Error,
+        {
+            self.visit_bytes(v.as_bytes())
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let mut array = [0; N];
+            for (index, byte) in array.iter_mut().enumerate() {
+                *byte = seq
+                    .next_element()?
+                    .ok_or_else(|| A::Error::invalid_length(index, &self))?;
+            }
+            if seq.next_element::<u8>()?.is_some() {
+                return Err(A::Error::invalid_length(N + 1, &self));
+            }
+            Ok(array)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{from_bytes, from_value, to_bytes, to_value};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct GetPeers {
+        #[serde(with = "binary_array")]
+        id: [u8; 4],
+        #[serde(with = "binary_array")]
+        info_hash: [u8; 2],
+    }
+
+    #[test]
+    fn test_binary_array() {
+        let input = b"d2:id4:\x00\x01\x02\x039:info_hash2:\xeb\x8be";
+        let message: GetPeers = from_bytes(input).unwrap();
+        assert_eq!(
+            message,
+            GetPeers {
+                id: [0, 1, 2, 3],
+                info_hash: [0xeb, 0x8b],
+            }
+        );
+        assert_eq!(to_bytes(&message).unwrap(), input);
+        assert_eq!(
+            from_value::<GetPeers>(to_value(&message).unwrap()).unwrap(),
+            message
+        );
+    }
+
+    #[test]
+    fn test_binary_array_wrong_length() {
+        for (input, length) in [
+            (&b"d2:id3:\x00\x01\x029:info_hash2:\xeb\x8be"[..], 3),
+            (b"d2:id5:\x00\x01\x02\x03\x049:info_hash2:\xeb\x8be", 5),
+        ] {
+            assert_eq!(
+                from_bytes::<GetPeers>(input).unwrap_err().to_string(),
+                format!(
+                    "Serde error: \"invalid length {}, expected a byte string of length 4\"",
+                    length
+                )
+            );
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Containers {
+        #[serde(with = "binary_string")]
+        boxed: Box<[u8]>,
+        #[serde(with = "binary_string")]
+        nodes: std::collections::BTreeMap<Vec<u8>, u16>,
+        #[serde(with = "binary_string")]
+        peers: std::collections::HashMap<Box<[u8]>, Option<i64>>,
+        #[serde(default, with = "binary_string")]
+        token: Option<Vec<u8>>,
+        #[serde(with = "binary_string")]
+        values: Vec<Vec<u8>>,
+    }
+
+    #[test]
+    fn test_binary_string_containers() {
+        let input = b"d5:boxed1:\x005:nodesd2:\xeb\x8bi6881ee5:peersd1:ai-1e1:bi2e1:ci3e1:di4e1:\xffi5ee5:token2:aa6:valuesl1:\x011:\x02ee";
+        let containers: Containers = from_bytes(input).unwrap();
+        assert_eq!(
+            containers,
+            Containers {
+                boxed: Box::new([0]),
+                nodes: [(vec![0xeb, 0x8b], 6881)].into(),
+                peers: [
+                    (&b"\xff"[..], Some(5)),
+                    (b"d", Some(4)),
+                    (b"c", Some(3)),
+                    (b"b", Some(2)),
+                    (b"a", Some(-1)),
+                ]
+                .into_iter()
+                .map(|(key, value)| (key.into(), value))
+                .collect(),
+                token: Some(b"aa".to_vec()),
+                values: vec![vec![1], vec![2]],
+            }
+        );
+        assert_eq!(to_bytes(&containers).unwrap(), input);
+        assert_eq!(
+            from_value::<Containers>(to_value(&containers).unwrap()).unwrap(),
+            containers
+        );
+
+        let empty: Containers = from_bytes(b"d5:boxed0:5:nodesde5:peersde6:valueslee").unwrap();
+        assert_eq!(empty.token, None);
+        assert_eq!(
+            to_bytes(&empty).unwrap(),
+            b"d5:boxed0:5:nodesde5:peersde6:valueslee"
+        );
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn test_binary_string_bytes() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Ping {
+            #[serde(with = "binary_string")]
+            id: bytes::Bytes,
+        }
+
+        let ping: Ping = from_bytes(b"d2:id2:\xeb\x8be").unwrap();
+        assert_eq!(ping.id, bytes::Bytes::from_static(b"\xeb\x8b"));
+        assert_eq!(to_bytes(&ping).unwrap(), b"d2:id2:\xeb\x8be");
     }
 }
@@ -0,0 +1,401 @@
+//! Adapters for the compact binary formats used by the DHT and trackers.
+//!
+//! IPv4 endpoints take 6 bytes and IPv6 endpoints 18: the address followed by
+//! the port, both in network byte order.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+
+use serde::de::{Error, Visitor};
+
+/// Node id and address of a DHT node, as found in the `nodes` field of
+/// `find_node` and `get_peers` responses (BEP 5).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CompactNode {
+    pub id: [u8; 20],
+    pub addr: SocketAddrV4,
+}
+
+impl CompactNode {
+    /// Length of the compact encoding: 20 bytes of id, 4 of IP and 2 of port.
+    pub const LEN: usize = 26;
+
+    pub fn from_bytes(bytes: &[u8; Self::LEN]) -> Self {
+        let mut id = [0; 20];
+        id.copy_from_slice(&bytes[..20]);
+        CompactNode {
+            id,
+            addr: v4_from_bytes(&bytes[20..]),
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::LEN] {
+        let mut bytes = [0; Self::LEN];
+        bytes[..20].copy_from_slice(&self.id);
+        bytes[20..].copy_from_slice(&v4_to_bytes(&self.addr));
+        bytes
+    }
+}
+
+fn v4_from_bytes(bytes: &[u8]) -> SocketAddrV4 {
+    let ip = Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]);
+    SocketAddrV4::new(ip, u16::from_be_bytes([bytes[4], bytes[5]]))
+}
+
+fn v4_to_bytes(addr: &SocketAddrV4) -> [u8; 6] {
+    let [a, b, c, d] = addr.ip().octets();
+    let [high, low] = addr.port().to_be_bytes();
+    [a, b, c, d, high, low]
+}
+
+fn v6_from_bytes(bytes: &[u8]) -> SocketAddrV6 {
+    let mut ip = [0; 16];
+    ip.copy_from_slice(&bytes[..16]);
+    let port = u16::from_be_bytes([bytes[16], bytes[17]]);
+    SocketAddrV6::new(Ipv6Addr::from(ip), port, 0, 0)
+}
+
+fn v6_to_bytes(addr: &SocketAddrV6) -> [u8; 18] {
+    let mut bytes = [0; 18];
+    bytes[..16].copy_from_slice(&addr.ip().octets());
+    bytes[16..].copy_from_slice(&addr.port().to_be_bytes());
+    bytes
+}
+
+fn addr_to_bytes(addr: &SocketAddr) -> Vec<u8> {
+    match addr {
+        SocketAddr::V4(addr) => v4_to_bytes(addr).to_vec(),
+        SocketAddr::V6(addr) => v6_to_bytes(addr).to_vec(),
+    }
+}
+
+/// Decodes a string of concatenated `N`-byte records.
+struct RecordsVisitor<T, const N: usize> {
+    decode: fn(&[u8]) -> T,
+    output: PhantomData<T>,
+}
+
+impl<T, const N: usize> RecordsVisitor<T, N> {
+    fn new(decode: fn(&[u8]) -> T) -> Self {
+        RecordsVisitor {
+            decode,
+            output: PhantomData,
+        }
+    }
+}
+
+impl<'de, T, const N: usize> Visitor<'de> for RecordsVisitor<T, N> {
+    type Value = Vec<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a byte string with a multiple of {} bytes", N)
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        let records = v.chunks_exact(N);
+        if !records.remainder().is_empty() {
+            return Err(E::invalid_length(v.len(), &self));
+        }
+        Ok(records.map(self.decode).collect())
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        self.visit_bytes(v.as_bytes())
+    }
+}
+
+/// Encodes `Vec<CompactNode>` as one string of concatenated 26-byte records.
+/// Decoding fails if the length of the string is not a multiple of 26.
+pub mod nodes {
+    use serde::{Deserializer, Serializer};
+
+    use super::{CompactNode, RecordsVisitor};
+
+    pub fn serialize<S>(nodes: &[CompactNode], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let bytes: Vec<u8> = nodes.iter().flat_map(CompactNode::to_bytes).collect();
+        serializer.serialize_bytes(&bytes)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<CompactNode>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let visitor = RecordsVisitor::<_, { CompactNode::LEN }>::new(|record| {
+            let mut bytes = [0; CompactNode::LEN];
+            bytes.copy_from_slice(record);
+            CompactNode::from_bytes(&bytes)
+        });
+        deserializer.deserialize_bytes(visitor)
+    }
+}
+
+/// Encodes a `SocketAddr` as a single 6-byte (IPv4) or 18-byte (IPv6) string.
+pub mod peer {
+    use std::fmt;
+    use std::net::SocketAddr;
+
+    use serde::de::{Error, Visitor};
+    use serde::{Deserializer, Serializer};
+
+    use super::{addr_to_bytes, v4_from_bytes, v6_from_bytes};
+
+    pub fn serialize<S>(addr: &SocketAddr, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(&addr_to_bytes(addr))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<SocketAddr, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_bytes(PeerVisitor)
+    }
+
+    pub(super) struct PeerVisitor;
+
+    impl<'de> Visitor<'de> for PeerVisitor {
+        type Value = SocketAddr;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a byte string of 6 or 18 bytes")
+        }
+
+        fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+        where
+            E: Error,
+        {
+            match v.len() {
+                6 => Ok(v4_from_bytes(v).into()),
+                18 => Ok(v6_from_bytes(v).into()),
+                len => Err(E::invalid_length(len, &self)),
+            }
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: Error,
+        {
+            self.visit_bytes(v.as_bytes())
+        }
+    }
+}
+
+/// Encodes `Vec<SocketAddr>` as a list of compact strings, like the `values`
+/// of a `get_peers` response (BEP 5). IPv4 and IPv6 peers can be mixed.
+pub mod peers {
+    use std::net::SocketAddr;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+    use serde_bytes::ByteBuf;
+
+    use super::{addr_to_bytes, peer};
+
+    pub fn serialize<S>(addrs: &[SocketAddr], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(addrs.iter().map(|addr| ByteBuf::from(addr_to_bytes(addr))))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<SocketAddr>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let peers = Vec::<Peer>::deserialize(deserializer)?;
+        Ok(peers.into_iter().map(|Peer(addr)| addr).collect())
+    }
+
+    struct Peer(SocketAddr);
+
+    impl<'de> Deserialize<'de> for Peer {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_bytes(peer::PeerVisitor).map(Peer)
+        }
+    }
+}
+
+/// Encodes `Vec<SocketAddrV4>` as one string of concatenated 6-byte records,
+/// like the tracker `peers` (BEP 23) and PEX `added` (BEP 11) fields.
+pub mod peers_v4 {
+    use std::net::SocketAddrV4;
+
+    use serde::{Deserializer, Serializer};
+
+    use super::{v4_from_bytes, v4_to_bytes, RecordsVisitor};
+
+    pub fn serialize<S>(addrs: &[SocketAddrV4], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let bytes: Vec<u8> = addrs.iter().flat_map(v4_to_bytes).collect();
+        serializer.serialize_bytes(&bytes)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<SocketAddrV4>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_bytes(RecordsVisitor::<_, 6>::new(v4_from_bytes))
+    }
+}
+
+/// Encodes `Vec<SocketAddrV6>` as one string of concatenated 18-byte records,
+/// like the tracker `peers6` (BEP 7) and PEX `added6` (BEP 11) fields.
+pub mod peers_v6 {
+    use std::net::SocketAddrV6;
+
+    use serde::{Deserializer, Serializer};
+
+    use super::{v6_from_bytes, v6_to_bytes, RecordsVisitor};
+
+    pub fn serialize<S>(addrs: &[SocketAddrV6], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let bytes: Vec<u8> = addrs.iter().flat_map(v6_to_bytes).collect();
+        serializer.serialize_bytes(&bytes)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<SocketAddrV6>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_bytes(RecordsVisitor::<_, 18>::new(v6_from_bytes))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{from_bytes, to_bytes};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct FindNodeResponse {
+        #[serde(with = "nodes")]
+        nodes: Vec<CompactNode>,
+    }
+
+    #[test]
+    fn test_nodes() {
+        let mut input = b"d5:nodes52:".to_vec();
+        input.extend_from_slice(&[1; 20]);
+        input.extend_from_slice(&[127, 0, 0, 1, 0x1a, 0xe1]);
+        input.extend_from_slice(&[2; 20]);
+        input.extend_from_slice(&[10, 0, 0, 2, 0, 80]);
+        input.push(b'e');
+
+        let response: FindNodeResponse = from_bytes(&input).unwrap();
+        assert_eq!(
+            response.nodes,
+            [
+                CompactNode {
+                    id: [1; 20],
+                    addr: SocketAddrV4::new(Ipv4Addr::LOCALHOST, 6881),
+                },
+                CompactNode {
+                    id: [2; 20],
+                    addr: SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), 80),
+                },
+            ]
+        );
+        assert_eq!(to_bytes(&response).unwrap(), input);
+
+        let empty: FindNodeResponse = from_bytes(b"d5:nodes0:e").unwrap();
+        assert!(empty.nodes.is_empty());
+    }
+
+    #[test]
+    fn test_nodes_wrong_length() {
+        let mut input = b"d5:nodes27:".to_vec();
+        input.extend_from_slice(&[0; 27]);
+        input.push(b'e');
+        assert_eq!(
+            from_bytes::<FindNodeResponse>(&input)
+                .unwrap_err()
+                .to_string(),
+            "Serde error: \"invalid length 27, expected a byte string with a multiple of 26 bytes\""
+        );
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Peers {
+        #[serde(with = "peers_v4")]
+        added: Vec<SocketAddrV4>,
+        #[serde(with = "peers_v6")]
+        added6: Vec<SocketAddrV6>,
+        #[serde(with = "peer")]
+        ip: SocketAddr,
+        #[serde(with = "peers")]
+        values: Vec<SocketAddr>,
+    }
+
+    #[test]
+    fn test_peers() {
+        let v4 = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), 6881);
+        let v6 = SocketAddrV6::new(Ipv6Addr::LOCALHOST, 80, 0, 0);
+        let mut input =
+            b"d5:added12:\x0a\x00\x00\x02\x1a\xe1\x0a\x00\x00\x02\x1a\xe16:added618:".to_vec();
+        input.extend_from_slice(&[0; 15]);
+        input.extend_from_slice(b"\x01\x00\x502:ip6:\x0a\x00\x00\x02\x1a\xe16:valuesl18:");
+        input.extend_from_slice(&[0; 15]);
+        input.extend_from_slice(b"\x01\x00\x506:\x0a\x00\x00\x02\x1a\xe1ee");
+
+        let peers: Peers = from_bytes(&input).unwrap();
+        assert_eq!(
+            peers,
+            Peers {
+                added: vec![v4, v4],
+                added6: vec![v6],
+                ip: v4.into(),
+                values: vec![v6.into(), v4.into()],
+            }
+        );
+        assert_eq!(to_bytes(&peers).unwrap(), input);
+    }
+
+    #[test]
+    fn test_peers_wrong_length() {
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Peer {
+            #[serde(with = "peer")]
+            ip: SocketAddr,
+        }
+
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Peers6 {
+            #[serde(with = "peers_v6")]
+            peers6: Vec<SocketAddrV6>,
+        }
+
+        assert_eq!(
+            from_bytes::<Peer>(b"d2:ip4:\x7f\x00\x00\x01e")
+                .unwrap_err()
+                .to_string(),
+            "Serde error: \"invalid length 4, expected a byte string of 6 or 18 bytes\""
+        );
+        assert_eq!(
+            from_bytes::<Peers6>(b"d6:peers66:\x7f\x00\x00\x01\x1a\xe1e")
+                .unwrap_err()
+                .to_string(),
+            "Serde error: \"invalid length 6, expected a byte string with a multiple of 18 bytes\""
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Unexpected, Visitor};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_bytes::Bytes;
+
+use super::{DebugBytes, Value};
+
+/// Borrowed counterpart of [`Value`] whose byte strings are slices of the
+/// input buffer, so large strings like `pieces` are never copied.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ValueRef<'a> {
+    Integer(i64),
+    Bytes(&'a [u8]),
+    List(Vec<ValueRef<'a>>),
+    Dict(BTreeMap<&'a [u8], ValueRef<'a>>),
+}
+
+impl ValueRef<'_> {
+    /// Copies the borrowed byte strings into an owned [`Value`].
+    pub fn into_owned(self) -> Value {
+        Value::from(&self)
+    }
+}
+
+impl From<&ValueRef<'_>> for Value {
+    fn from(value: &ValueRef<'_>) -> Self {
+        match value {
+            ValueRef::Integer(integer) => Value::Integer(*integer),
+            ValueRef::Bytes(bytes) => Value::Bytes(bytes.to_vec()),
+            ValueRef::List(list) => Value::List(list.iter().map(Value::from).collect()),
+            ValueRef::Dict(dict) => Value::Dict(
+                dict.iter()
+                    .map(|(key, value)| (key.to_vec(), Value::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<ValueRef<'_>> for Value {
+    fn from(value: ValueRef<'_>) -> Self {
+        Value::from(&value)
+    }
+}
+
+impl fmt::Debug for ValueRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueRef::Integer(integer) => fmt::Debug::fmt(integer, f),
+            ValueRef::Bytes(bytes) => fmt::Debug::fmt(&DebugBytes(bytes), f),
+            ValueRef::List(list) => f.debug_list().entries(list).finish(),
+            ValueRef::Dict(dict) => f
+                .debug_map()
+                .entries(dict.iter().map(|(key, value)| (DebugBytes(key), value)))
+                .finish(),
+        }
+    }
+}
+
+impl Serialize for ValueRef<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            ValueRef::Integer(integer) => serializer.serialize_i64(*integer),
+            ValueRef::Bytes(bytes) => serializer.serialize_bytes(bytes),
+            ValueRef::List(list) => serializer.collect_seq(list),
+            ValueRef::Dict(dict) => {
+                let mut map = serializer.serialize_map(Some(dict.len()))?;
+                for (key, value) in dict {
+                    map.serialize_entry(Bytes::new(key), value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+struct ValueRefVisitor;
+
+impl<'de> Visitor<'de> for ValueRefVisitor {
+    type Value = ValueRef<'de>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any bencode value borrowed from the input")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(ValueRef::Integer(v.into()))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(ValueRef::Integer(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        i64::try_from(v)
+            .map(ValueRef::Integer)
+            .map_err(|_| E::invalid_value(Unexpected::Unsigned(v), &"a 64-bit signed integer"))
+    }
+
+    fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E> {
+        Ok(ValueRef::Bytes(v.as_bytes()))
+    }
+
+    fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<Self::Value, E> {
+        Ok(ValueRef::Bytes(v))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut list = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(value) = seq.next_element()? {
+            list.push(value);
+        }
+        Ok(ValueRef::List(list))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut dict = BTreeMap::new();
+        while let Some((key, value)) = map.next_entry::<&'de [u8], ValueRef<'de>>()? {
+            dict.insert(key, value);
+        }
+        Ok(ValueRef::Dict(dict))
+    }
+}
+
+impl<'de> Deserialize<'de> for ValueRef<'de> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ValueRefVisitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{from_bytes, to_bytes};
+
+    #[test]
+    fn test_borrows_input() {
+        let input = b"d6:lengthi12e6:pieces4:\x00\x01\x02\x03e";
+        let value: ValueRef = from_bytes(input).unwrap();
+        let ValueRef::Dict(dict) = &value else {
+            panic!("expected a dictionary, got {:?}", value);
+        };
+        let Some(ValueRef::Bytes(pieces)) = dict.get(&b"pieces"[..]) else {
+            panic!("expected pieces, got {:?}", value);
+        };
+        assert_eq!(pieces.as_ptr(), input[23..].as_ptr());
+        assert_eq!(
+            format!("{:?}", value),
+            r#"{"length": 12, "pieces": 0x00010203}"#
+        );
+
+        assert_eq!(to_bytes(&value).unwrap(), input);
+        assert_eq!(value.into_owned(), from_bytes::<Value>(input).unwrap());
+    }
+}
@@ -0,0 +1,480 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::de::value::{BorrowedStrDeserializer, MapDeserializer, SeqDeserializer};
+use serde::de::{
+    Deserialize, DeserializeOwned, DeserializeSeed, Deserializer, EnumAccess, Error,
+    IntoDeserializer, MapAccess, SeqAccess, Unexpected, VariantAccess, Visitor,
+};
+use serde::forward_to_deserialize_any;
+use serde_bytes::ByteBuf;
+
+use super::Value;
+use crate::de::Integer;
+use crate::raw;
+use crate::ser::to_bytes;
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any bencode value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(Value::Integer(v.into()))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(Value::Integer(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        i64::try_from(v)
+            .map(Value::Integer)
+            .map_err(|_| E::invalid_value(Unexpected::Unsigned(v), &"a 64-bit signed integer"))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(Value::Bytes(v.as_bytes().to_vec()))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Self::Value, E> {
+        Ok(Value::Bytes(v.into_bytes()))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(Value::Bytes(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(Value::Bytes(v))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut list = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(value) = seq.next_element()? {
+            list.push(value);
+        }
+        Ok(Value::List(list))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut dict = BTreeMap::new();
+        while let Some((key, value)) = map.next_entry::<ByteBuf, Value>()? {
+            dict.insert(key.into_vec(), value);
+        }
+        Ok(Value::Dict(dict.into()))
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+fn visit_bool<'de, V>(integer: i64, visitor: V) -> Result<V::Value, crate::Error>
+where
+    V: Visitor<'de>,
+{
+    match integer {
+        0 => visitor.visit_bool(false),
+        1 => visitor.visit_bool(true),
+        _ => Err(crate::Error::ExpectedBoolean),
+    }
+}
+
+/// Deserializes the value of a single-entry dictionary as an enum variant.
+struct EnumDeserializer<K, V> {
+    variant: K,
+    value: V,
+}
+
+impl<'de, K, V> EnumAccess<'de> for EnumDeserializer<K, V>
+where
+    K: IntoDeserializer<'de, crate::Error>,
+    V: IntoDeserializer<'de, crate::Error>,
+{
+    type Error = crate::Error;
+
+    type Variant = VariantDeserializer<V>;
+
+    fn variant_seed<S>(self, seed: S) -> Result<(S::Value, Self::Variant), Self::Error>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(self.variant.into_deserializer())?;
+        Ok((variant, VariantDeserializer { value: self.value }))
+    }
+}
+
+struct VariantDeserializer<V> {
+    value: V,
+}
+
+impl<'de, V> VariantAccess<'de> for VariantDeserializer<V>
+where
+    V: IntoDeserializer<'de, crate::Error>,
+{
+    type Error = crate::Error;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        Err(crate::Error::ExpectedString)
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(self.value.into_deserializer())
+    }
+
+    fn tuple_variant<W>(self, _len: usize, visitor: W) -> Result<W::Value, Self::Error>
+    where
+        W: Visitor<'de>,
+    {
+        self.value.into_deserializer().deserialize_seq(visitor)
+    }
+
+    fn struct_variant<W>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: W,
+    ) -> Result<W::Value, Self::Error>
+    where
+        W: Visitor<'de>,
+    {
+        self.value.into_deserializer().deserialize_map(visitor)
+    }
+}
+
+impl<'de> IntoDeserializer<'de, crate::Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+impl<'de> Deserializer<'de> for Value {
+    type Error = crate::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Integer(integer) => Integer::from(integer).visit(visitor),
+            Value::Bytes(bytes) => match String::from_utf8(bytes) {
+                Ok(string) => visitor.visit_string(string),
+                Err(err) => visitor.visit_byte_buf(err.into_bytes()),
+            },
+            Value::List(list) => {
+                let mut seq = SeqDeserializer::new(list.into_iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            Value::Dict(dict) => {
+                let mut map = MapDeserializer::new(
+                    dict.into_iter()
+                        .map(|(key, value)| (Value::Bytes(key), value)),
+                );
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+        }
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Integer(integer) => visit_bool(integer, visitor),
+            _ => Err(crate::Error::ExpectedInteger),
+        }
+    }
+
+    fn deserialize_f32<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(crate::Error::InvalidValue("Cannot deserialize f32"))
+    }
+
+    fn deserialize_f64<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(crate::Error::InvalidValue("Cannot deserialize f64"))
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if name == raw::TOKEN {
+            return visitor.visit_byte_buf(to_bytes(&self)?);
+        }
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Bytes(bytes) => visitor.visit_enum(
+                String::from_utf8(bytes)
+                    .map_err(|_| crate::Error::ExpectedString)?
+                    .into_deserializer(),
+            ),
+            Value::Dict(dict) if dict.len() == 1 => {
+                let (variant, value) = dict.into_iter().next().ok_or(crate::Error::ExpectedEnum)?;
+                visitor.visit_enum(EnumDeserializer {
+                    variant: Value::Bytes(variant),
+                    value,
+                })
+            }
+            _ => Err(crate::Error::ExpectedEnum),
+        }
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 char str string bytes byte_buf
+        seq tuple tuple_struct map struct identifier
+    }
+}
+
+impl<'de> IntoDeserializer<'de, crate::Error> for &'de Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+/// Deserializer for borrowed dictionary keys.
+struct KeyDeserializer<'de>(&'de [u8]);
+
+impl<'de> IntoDeserializer<'de, crate::Error> for KeyDeserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+impl<'de> Deserializer<'de> for KeyDeserializer<'de> {
+    type Error = crate::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match std::str::from_utf8(self.0) {
+            Ok(key) => visitor.visit_borrowed_str(key),
+            Err(_) => visitor.visit_borrowed_bytes(self.0),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'de> Deserializer<'de> for &'de Value {
+    type Error = crate::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Integer(integer) => Integer::from(*integer).visit(visitor),
+            Value::Bytes(bytes) => KeyDeserializer(bytes).deserialize_any(visitor),
+            Value::List(list) => {
+                let mut seq = SeqDeserializer::new(list.iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            Value::Dict(dict) => {
+                let mut map = MapDeserializer::new(
+                    dict.iter()
+                        .map(|(key, value)| (KeyDeserializer(key), value)),
+                );
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+        }
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Integer(integer) => visit_bool(*integer, visitor),
+            _ => Err(crate::Error::ExpectedInteger),
+        }
+    }
+
+    fn deserialize_f32<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(crate::Error::InvalidValue("Cannot deserialize f32"))
+    }
+
+    fn deserialize_f64<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(crate::Error::InvalidValue("Cannot deserialize f64"))
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if name == raw::TOKEN {
+            return visitor.visit_byte_buf(to_bytes(&self)?);
+        }
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Bytes(bytes) => visitor.visit_enum(BorrowedStrDeserializer::new(
+                std::str::from_utf8(bytes).map_err(|_| crate::Error::ExpectedString)?,
+            )),
+            Value::Dict(dict) if dict.len() == 1 => {
+                let (variant, value) = dict.iter().next().ok_or(crate::Error::ExpectedEnum)?;
+                visitor.visit_enum(EnumDeserializer {
+                    variant: KeyDeserializer(variant),
+                    value,
+                })
+            }
+            _ => Err(crate::Error::ExpectedEnum),
+        }
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 char str string bytes byte_buf
+        seq tuple tuple_struct map struct identifier
+    }
+}
+
+/// Converts a [`Value`] into any type implementing `Deserialize`.
+pub fn from_value<T>(value: Value) -> Result<T, crate::Error>
+where
+    T: DeserializeOwned,
+{
+    T::deserialize(value)
+}
@@ -0,0 +1,493 @@
+use std::collections::{btree_map, BTreeMap};
+use std::fmt;
+use std::ops::Deref;
+
+use super::{escape_key, Value};
+use crate::error::Error;
+
+/// A bencode dictionary: byte string keys in canonical, sorted order.
+///
+/// Besides the usual map accessors, a [`Dict`] can be built fluently, e.g.
+/// `Dict::new().insert("q", "ping")`, and read with typed getters that report
+/// which key was missing or of the wrong type.
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Dict(BTreeMap<Vec<u8>, Value>);
+
+impl Dict {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an entry, replacing any previous value of `key`, and returns the
+    /// dictionary for chaining.
+    pub fn insert<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: Into<Vec<u8>>,
+        V: Into<Value>,
+    {
+        self.0.insert(key.into(), value.into());
+        self
+    }
+
+    /// Inserts or replaces the value of `key`, returning the previous one.
+    pub fn set<K, V>(&mut self, key: K, value: V) -> Option<Value>
+    where
+        K: Into<Vec<u8>>,
+        V: Into<Value>,
+    {
+        self.0.insert(key.into(), value.into())
+    }
+
+    pub fn remove<K>(&mut self, key: K) -> Option<Value>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.0.remove(key.as_ref())
+    }
+
+    /// Deep-merges `other` into `self`: dictionaries present on both sides are
+    /// merged recursively, any other value from `other` replaces the one in
+    /// `self`.
+    pub fn merge(&mut self, other: Dict) {
+        for (key, value) in other {
+            match (self.0.get_mut(&key), value) {
+                (Some(Value::Dict(dict)), Value::Dict(other)) => dict.merge(other),
+                (_, value) => {
+                    self.0.insert(key, value);
+                }
+            }
+        }
+    }
+
+    pub fn get<K>(&self, key: K) -> Option<&Value>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.0.get(key.as_ref())
+    }
+
+    pub fn get_mut<K>(&mut self, key: K) -> Option<&mut Value>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.0.get_mut(key.as_ref())
+    }
+
+    pub fn contains_key<K>(&self, key: K) -> bool
+    where
+        K: AsRef<[u8]>,
+    {
+        self.0.contains_key(key.as_ref())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterates over the entries in key order.
+    pub fn iter(&self) -> btree_map::Iter<'_, Vec<u8>, Value> {
+        self.0.iter()
+    }
+
+    pub fn keys(&self) -> btree_map::Keys<'_, Vec<u8>, Value> {
+        self.0.keys()
+    }
+
+    pub fn values(&self) -> btree_map::Values<'_, Vec<u8>, Value> {
+        self.0.values()
+    }
+
+    pub fn values_mut(&mut self) -> btree_map::ValuesMut<'_, Vec<u8>, Value> {
+        self.0.values_mut()
+    }
+
+    pub fn get_int<K>(&self, key: K) -> Result<i64, Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.view().get_int(key)
+    }
+
+    pub fn get_bytes<K>(&self, key: K) -> Result<&[u8], Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.view().get_bytes(key)
+    }
+
+    /// Like [`Dict::get_bytes`], but also requires the bytes to be UTF-8.
+    pub fn get_str<K>(&self, key: K) -> Result<&str, Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.view().get_str(key)
+    }
+
+    pub fn get_list<K>(&self, key: K) -> Result<ListView<'_>, Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.view().get_list(key)
+    }
+
+    pub fn get_dict<K>(&self, key: K) -> Result<DictView<'_>, Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.view().get_dict(key)
+    }
+
+    fn view(&self) -> DictView<'_> {
+        DictView {
+            dict: self,
+            path: String::new(),
+        }
+    }
+}
+
+/// A dictionary reached through [`Dict::get_dict`] or [`ListView::get_dict`].
+///
+/// It dereferences to the [`Dict`] and remembers the pointer it was found
+/// at, so errors from its typed getters name the full path, e.g. `/a/port`
+/// for `dict.get_dict("a")?.get_int("port")`.
+#[derive(Clone, Debug)]
+pub struct DictView<'a> {
+    dict: &'a Dict,
+    path: String,
+}
+
+impl<'a> DictView<'a> {
+    /// Pointer to this dictionary from the one the lookups started at.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn get_int<K>(&self, key: K) -> Result<i64, Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.typed(key.as_ref(), "integer", Value::as_int)
+    }
+
+    pub fn get_bytes<K>(&self, key: K) -> Result<&'a [u8], Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.typed(key.as_ref(), "byte string", Value::as_bytes)
+    }
+
+    pub fn get_str<K>(&self, key: K) -> Result<&'a str, Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.typed(key.as_ref(), "UTF-8 string", Value::as_str)
+    }
+
+    pub fn get_list<K>(&self, key: K) -> Result<ListView<'a>, Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        let key = key.as_ref();
+        let list = self.typed(key, "list", Value::as_list)?;
+        Ok(ListView {
+            list,
+            path: self.child(key),
+        })
+    }
+
+    pub fn get_dict<K>(&self, key: K) -> Result<DictView<'a>, Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        let key = key.as_ref();
+        let dict = self.typed(key, "dictionary", Value::as_dict)?;
+        Ok(DictView {
+            dict,
+            path: self.child(key),
+        })
+    }
+
+    fn child(&self, key: &[u8]) -> String {
+        format!("{}/{}", self.path, escape_key(key))
+    }
+
+    /// Casts the value of `key`, naming it in the error if it is missing or
+    /// of another type.
+    fn typed<T>(
+        &self,
+        key: &[u8],
+        expected: &'static str,
+        cast: fn(&'a Value) -> Option<T>,
+    ) -> Result<T, Error> {
+        typed(self.dict.get(key), || self.child(key), expected, cast)
+    }
+}
+
+impl Deref for DictView<'_> {
+    type Target = Dict;
+
+    fn deref(&self) -> &Dict {
+        self.dict
+    }
+}
+
+/// A list reached through [`Dict::get_list`] or [`ListView::get_list`].
+///
+/// It dereferences to the slice of elements and, like [`DictView`], reports
+/// the full path of elements that are missing or of the wrong type.
+#[derive(Clone, Debug)]
+pub struct ListView<'a> {
+    list: &'a [Value],
+    path: String,
+}
+
+impl<'a> ListView<'a> {
+    /// Pointer to this list from the dictionary the lookups started at.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn get_int(&self, index: usize) -> Result<i64, Error> {
+        self.typed(index, "integer", Value::as_int)
+    }
+
+    pub fn get_bytes(&self, index: usize) -> Result<&'a [u8], Error> {
+        self.typed(index, "byte string", Value::as_bytes)
+    }
+
+    pub fn get_str(&self, index: usize) -> Result<&'a str, Error> {
+        self.typed(index, "UTF-8 string", Value::as_str)
+    }
+
+    pub fn get_list(&self, index: usize) -> Result<ListView<'a>, Error> {
+        let list = self.typed(index, "list", Value::as_list)?;
+        Ok(ListView {
+            list,
+            path: self.child(index),
+        })
+    }
+
+    pub fn get_dict(&self, index: usize) -> Result<DictView<'a>, Error> {
+        let dict = self.typed(index, "dictionary", Value::as_dict)?;
+        Ok(DictView {
+            dict,
+            path: self.child(index),
+        })
+    }
+
+    fn child(&self, index: usize) -> String {
+        format!("{}/{}", self.path, index)
+    }
+
+    fn typed<T>(
+        &self,
+        index: usize,
+        expected: &'static str,
+        cast: fn(&'a Value) -> Option<T>,
+    ) -> Result<T, Error> {
+        typed(self.list.get(index), || self.child(index), expected, cast)
+    }
+}
+
+impl Deref for ListView<'_> {
+    type Target = [Value];
+
+    fn deref(&self) -> &[Value] {
+        self.list
+    }
+}
+
+fn typed<'a, T>(
+    value: Option<&'a Value>,
+    path: impl Fn() -> String,
+    expected: &'static str,
+    cast: fn(&'a Value) -> Option<T>,
+) -> Result<T, Error> {
+    let value = value.ok_or_else(|| Error::MissingKey(path()))?;
+    cast(value).ok_or_else(|| Error::UnexpectedType {
+        path: path(),
+        expected,
+    })
+}
+
+impl fmt::Debug for Dict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(
+                self.iter()
+                    .map(|(key, value)| (super::DebugBytes(key), value)),
+            )
+            .finish()
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for Dict
+where
+    K: Into<Vec<u8>>,
+    V: Into<Value>,
+{
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        Dict(
+            iter.into_iter()
+                .map(|(key, value)| (key.into(), value.into()))
+                .collect(),
+        )
+    }
+}
+
+impl<K, V> Extend<(K, V)> for Dict
+where
+    K: Into<Vec<u8>>,
+    V: Into<Value>,
+{
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        self.0.extend(
+            iter.into_iter()
+                .map(|(key, value)| (key.into(), value.into())),
+        );
+    }
+}
+
+impl IntoIterator for Dict {
+    type Item = (Vec<u8>, Value);
+
+    type IntoIter = btree_map::IntoIter<Vec<u8>, Value>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Dict {
+    type Item = (&'a Vec<u8>, &'a Value);
+
+    type IntoIter = btree_map::Iter<'a, Vec<u8>, Value>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Dict {
+    type Item = (&'a Vec<u8>, &'a mut Value);
+
+    type IntoIter = btree_map::IterMut<'a, Vec<u8>, Value>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter_mut()
+    }
+}
+
+impl From<BTreeMap<Vec<u8>, Value>> for Dict {
+    fn from(map: BTreeMap<Vec<u8>, Value>) -> Self {
+        Dict(map)
+    }
+}
+
+impl From<Dict> for BTreeMap<Vec<u8>, Value> {
+    fn from(dict: Dict) -> Self {
+        dict.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{from_bytes, to_bytes};
+
+    #[test]
+    fn test_builder() {
+        let query = Dict::new()
+            .insert("t", b"aa")
+            .insert("y", "q")
+            .insert("q", "ping")
+            .insert("a", Dict::new().insert("id", &b"\xeb\x8b"[..]));
+        assert_eq!(
+            to_bytes(&Value::from(query)).unwrap(),
+            b"d1:ad2:id2:\xeb\x8be1:q4:ping1:t2:aa1:y1:qe"
+        );
+    }
+
+    #[test]
+    fn test_typed_getters() {
+        let value: Value = from_bytes(b"d1:ad2:id2:\xeb\x8b4:porti6881ee1:ql1:xee").unwrap();
+        let dict = value.as_dict().unwrap();
+        let args = dict.get_dict(b"a").unwrap();
+        assert_eq!(args.get_int(b"port").unwrap(), 6881);
+        assert_eq!(args.get_bytes(b"id").unwrap(), b"\xeb\x8b");
+        assert_eq!(*dict.get_list(b"q").unwrap(), [Value::from("x")]);
+        assert_eq!(args.len(), 2);
+        assert_eq!(args.path(), "/a");
+
+        assert!(matches!(
+            args.get_str(b"id"),
+            Err(Error::UnexpectedType { path, expected: "UTF-8 string" }) if path == "/a/id"
+        ));
+        assert!(matches!(
+            args.get_int(b"missing"),
+            Err(Error::MissingKey(path)) if path == "/a/missing"
+        ));
+        let list = dict.get_list(b"q").unwrap();
+        assert_eq!(list.get_str(0).unwrap(), "x");
+        assert!(matches!(
+            list.get_int(0),
+            Err(Error::UnexpectedType { path, expected: "integer" }) if path == "/q/0"
+        ));
+        assert!(matches!(
+            list.get_dict(1),
+            Err(Error::MissingKey(path)) if path == "/q/1"
+        ));
+        assert!(matches!(
+            dict.get_int(b"a/b"),
+            Err(Error::MissingKey(path)) if path == "/a~1b"
+        ));
+    }
+
+    #[test]
+    fn test_set_and_remove() {
+        let mut dict = Dict::new().insert("b", 2);
+        assert_eq!(dict.set("c", 3), None);
+        assert_eq!(dict.set("a", 1), None);
+        assert_eq!(dict.set("b", "two"), Some(Value::Integer(2)));
+        assert_eq!(dict.remove(b"c"), Some(Value::Integer(3)));
+        assert_eq!(dict.remove(b"c"), None);
+        assert_eq!(to_bytes(&Value::from(dict)).unwrap(), b"d1:ai1e1:b3:twoe");
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut template = Dict::new()
+            .insert("announce", "http://tracker")
+            .insert(
+                "info",
+                Dict::new().insert("name", "template").insert("private", 1),
+            )
+            .insert("url-list", vec![Value::from("a")]);
+        let overlay = Dict::new()
+            .insert("info", Dict::new().insert("name", "spam"))
+            .insert("url-list", vec![Value::from("b")])
+            .insert("comment", "eggs");
+        template.merge(overlay);
+        assert_eq!(
+            template,
+            Dict::new()
+                .insert("announce", "http://tracker")
+                .insert("comment", "eggs")
+                .insert(
+                    "info",
+                    Dict::new().insert("name", "spam").insert("private", 1)
+                )
+                .insert("url-list", vec![Value::from("b")])
+        );
+    }
+}
@@ -0,0 +1,342 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::error::Error;
+
+mod borrowed;
+mod de;
+mod dict;
+mod patch;
+mod pointer;
+mod ser;
+
+pub use borrowed::ValueRef;
+pub use de::from_value;
+pub use dict::{Dict, DictView, ListView};
+pub use patch::Patch;
+pub use pointer::escape_key;
+pub use ser::to_value;
+
+/// Any bencode value, for documents without a matching Rust type.
+///
+/// Dictionaries are keyed by raw byte strings and kept sorted, which is the
+/// canonical order required by the bencode specification.
+///
+/// Integers are held as `i64`. Larger ones, such as `u64` values above
+/// `i64::MAX` that a typed `u64` field would accept, cannot be represented:
+/// decoding or converting them fails with [`Error::LargeNumber`].
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Value {
+    Integer(i64),
+    Bytes(Vec<u8>),
+    List(Vec<Value>),
+    Dict(Dict),
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Integer(integer) => fmt::Debug::fmt(integer, f),
+            Value::Bytes(bytes) => fmt::Debug::fmt(&DebugBytes(bytes), f),
+            Value::List(list) => f.debug_list().entries(list).finish(),
+            Value::Dict(dict) => fmt::Debug::fmt(dict, f),
+        }
+    }
+}
+
+impl Value {
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Value::Integer(integer) => Some(*integer),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// Returns the byte string if it is valid UTF-8.
+    pub fn as_str(&self) -> Option<&str> {
+        std::str::from_utf8(self.as_bytes()?).ok()
+    }
+
+    pub fn as_list(&self) -> Option<&[Value]> {
+        match self {
+            Value::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    pub fn as_dict(&self) -> Option<&Dict> {
+        match self {
+            Value::Dict(dict) => Some(dict),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! from_integer {
+    ($($ty:ty)*) => {
+        $(
+            impl From<$ty> for Value {
+                fn from(integer: $ty) -> Self {
+                    Value::Integer(integer.into())
+                }
+            }
+        )*
+    };
+}
+
+from_integer!(i8 i16 i32 i64 u8 u16 u32 bool);
+
+// Bencode integers are read into an i64, so the wider types only convert
+// when they fit, failing with Error::LargeNumber like to_value does.
+macro_rules! try_from_integer {
+    ($($ty:ty)*) => {
+        $(
+            impl TryFrom<$ty> for Value {
+                type Error = Error;
+
+                fn try_from(integer: $ty) -> Result<Self, Error> {
+                    i64::try_from(integer)
+                        .map(Value::Integer)
+                        .map_err(|_| Error::LargeNumber)
+                }
+            }
+        )*
+    };
+}
+
+try_from_integer!(u64 usize isize);
+
+impl From<&str> for Value {
+    fn from(string: &str) -> Self {
+        Value::Bytes(string.as_bytes().to_vec())
+    }
+}
+
+impl From<String> for Value {
+    fn from(string: String) -> Self {
+        Value::Bytes(string.into_bytes())
+    }
+}
+
+impl From<&[u8]> for Value {
+    fn from(bytes: &[u8]) -> Self {
+        Value::Bytes(bytes.to_vec())
+    }
+}
+
+impl<const N: usize> From<&[u8; N]> for Value {
+    fn from(bytes: &[u8; N]) -> Self {
+        Value::Bytes(bytes.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(bytes: Vec<u8>) -> Self {
+        Value::Bytes(bytes)
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(list: Vec<Value>) -> Self {
+        Value::List(list)
+    }
+}
+
+impl<T> FromIterator<T> for Value
+where
+    T: Into<Value>,
+{
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        Value::List(iter.into_iter().map(Into::into).collect())
+    }
+}
+
+impl From<Dict> for Value {
+    fn from(dict: Dict) -> Self {
+        Value::Dict(dict)
+    }
+}
+
+impl<K, V> From<BTreeMap<K, V>> for Value
+where
+    K: Into<Vec<u8>>,
+    V: Into<Value>,
+{
+    fn from(map: BTreeMap<K, V>) -> Self {
+        Value::Dict(map.into_iter().collect())
+    }
+}
+
+impl<K, V> From<HashMap<K, V>> for Value
+where
+    K: Into<Vec<u8>>,
+    V: Into<Value>,
+{
+    fn from(map: HashMap<K, V>) -> Self {
+        Value::Dict(map.into_iter().collect())
+    }
+}
+
+/// Formats a byte string as quoted text when it is printable UTF-8 and as
+/// hex otherwise.
+pub(crate) struct DebugBytes<'a>(pub &'a [u8]);
+
+impl fmt::Debug for DebugBytes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match std::str::from_utf8(self.0) {
+            Ok(text) if !text.chars().any(char::is_control) => fmt::Debug::fmt(text, f),
+            _ => {
+                f.write_str("0x")?;
+                for byte in self.0 {
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{from_bytes, to_bytes};
+
+    #[test]
+    fn test_round_trip() {
+        let input = b"d1:ad2:id2:\xeb\x8be1:q4:ping1:t2:aa1:v4:LT\x01\x2f1:y1:qe";
+        let value: Value = from_bytes(input).unwrap();
+
+        let mut args = BTreeMap::new();
+        args.insert(b"id".to_vec(), Value::Bytes(vec![0xeb, 0x8b]));
+        let mut dict = BTreeMap::new();
+        dict.insert(b"a".to_vec(), Value::Dict(args.into()));
+        dict.insert(b"q".to_vec(), Value::Bytes(b"ping".to_vec()));
+        dict.insert(b"t".to_vec(), Value::Bytes(b"aa".to_vec()));
+        dict.insert(b"v".to_vec(), Value::Bytes(b"LT\x01\x2f".to_vec()));
+        dict.insert(b"y".to_vec(), Value::Bytes(b"q".to_vec()));
+        assert_eq!(value, Value::Dict(dict.into()));
+
+        assert_eq!(to_bytes(&value).unwrap(), input);
+    }
+
+    #[test]
+    fn test_integers_and_lists() {
+        let value: Value = from_bytes(b"li-9223372036854775808ei0eli1eee").unwrap();
+        assert_eq!(
+            value,
+            Value::List(vec![
+                Value::Integer(i64::MIN),
+                Value::Integer(0),
+                Value::List(vec![Value::Integer(1)]),
+            ])
+        );
+        assert!(matches!(
+            from_bytes::<Value>(b"i9223372036854775808e"),
+            Err(Error::LargeNumber)
+        ));
+        assert!(matches!(
+            from_bytes::<Value>(b"li18446744073709551615ee"),
+            Err(Error::LargeNumber)
+        ));
+        assert!(matches!(
+            from_bytes::<ValueRef>(b"i18446744073709551615e"),
+            Err(Error::LargeNumber)
+        ));
+        assert!(matches!(
+            crate::to_value(&u64::MAX),
+            Err(Error::LargeNumber)
+        ));
+
+        assert_eq!(Value::try_from(7u64).unwrap(), Value::Integer(7));
+        assert_eq!(Value::try_from(7usize).unwrap(), Value::Integer(7));
+        assert!(matches!(Value::try_from(u64::MAX), Err(Error::LargeNumber)));
+    }
+
+    #[test]
+    fn test_sorts_keys() {
+        let value: Value = from_bytes(b"d1:bi2e1:ai1ee").unwrap();
+        assert_eq!(to_bytes(&value).unwrap(), b"d1:ai1e1:bi2ee");
+    }
+
+    #[test]
+    fn test_typed_conversions() {
+        use crate::utils::binary_string;
+        use crate::{from_value, to_value};
+        use serde::{Deserialize, Serialize};
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+        enum Kind {
+            Unit,
+            Newtype(u8),
+            Optional(Option<u8>),
+            Tuple(i8, String),
+            Struct { id: u32 },
+        }
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+        struct Packet {
+            #[serde(with = "binary_string")]
+            id: Vec<u8>,
+            token: Option<String>,
+            implied_port: bool,
+            kinds: Vec<Kind>,
+            tuple: (u16, char),
+        }
+
+        let packet = Packet {
+            id: vec![0xeb, 0x8b],
+            token: None,
+            implied_port: true,
+            kinds: vec![
+                Kind::Unit,
+                Kind::Newtype(7),
+                Kind::Tuple(-1, "x".into()),
+                Kind::Struct { id: 3 },
+            ],
+            tuple: (6881, 'é'),
+        };
+
+        let value = to_value(&packet).unwrap();
+        let bytes = to_bytes(&packet).unwrap();
+        assert_eq!(value, from_bytes::<Value>(&bytes).unwrap());
+        assert_eq!(from_value::<Packet>(value.clone()).unwrap(), packet);
+        assert_eq!(Packet::deserialize(&value).unwrap(), packet);
+
+        let Value::Dict(mut dict) = value else {
+            panic!("expected a dictionary");
+        };
+        *dict.get_mut(b"implied_port").unwrap() = Value::Integer(2);
+        assert!(matches!(
+            from_value::<Packet>(Value::Dict(dict)),
+            Err(crate::Error::ExpectedBoolean)
+        ));
+        for kind in [Kind::Optional(None), Kind::Optional(Some(1))] {
+            assert_eq!(
+                to_value(&kind).unwrap(),
+                from_bytes::<Value>(&to_bytes(&kind).unwrap()).unwrap()
+            );
+        }
+        assert!(to_value(&None::<u8>).is_err());
+        assert!(to_value(&1.5f64).is_err());
+        assert!(to_value(&u64::MAX).is_err());
+    }
+
+    #[test]
+    fn test_debug() {
+        let value: Value = from_bytes(b"d2:id2:\xeb\x8b4:porti80e1:ql4:pingee").unwrap();
+        assert_eq!(
+            format!("{:?}", value),
+            r#"{"id": 0xeb8b, "port": 80, "q": ["ping"]}"#
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use super::pointer::{index, split_last};
+use super::Value;
+use crate::error::Error;
+
+/// A single structural edit addressed by a pointer, see [`Value::pointer`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Patch {
+    /// Replaces the value at `path`. The parent must exist: a missing
+    /// dictionary key is added, and a list index equal to the length of the
+    /// list appends to it.
+    Set { path: String, value: Value },
+    /// Removes an existing dictionary key or list element.
+    Remove { path: String },
+}
+
+/// What it takes to revert one applied patch. Containers are addressed by
+/// pointer, which stays valid because undo steps run in reverse order.
+enum Undo {
+    Root(Value),
+    Dict {
+        parent: String,
+        key: Vec<u8>,
+        previous: Option<Value>,
+    },
+    Replace {
+        parent: String,
+        index: usize,
+        previous: Value,
+    },
+    Pop {
+        parent: String,
+    },
+    Insert {
+        parent: String,
+        index: usize,
+        value: Value,
+    },
+}
+
+impl Value {
+    /// Applies `patches` in order. Either all of them succeed or `self` is
+    /// left untouched and the first error is returned.
+    ///
+    /// Patches are applied in place; on error the ones already applied are
+    /// reverted from an undo log, so only replaced values are kept aside.
+    pub fn patch<I>(&mut self, patches: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = Patch>,
+    {
+        let mut undo = Vec::new();
+        for patch in patches {
+            match self.apply(patch) {
+                Ok(step) => undo.push(step),
+                Err(error) => {
+                    for step in undo.into_iter().rev() {
+                        self.revert(step);
+                    }
+                    return Err(error);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, patch: Patch) -> Result<Undo, Error> {
+        let (path, value) = match patch {
+            Patch::Set { path, value } => (path, Some(value)),
+            Patch::Remove { path } => (path, None),
+        };
+        let value = match value {
+            Some(value) if path.is_empty() => {
+                return Ok(Undo::Root(std::mem::replace(self, value)));
+            }
+            value => value,
+        };
+        let (parent, key) = split_last(&path).ok_or_else(|| Error::InvalidPointer(path.clone()))?;
+        let container = self
+            .pointer_mut(parent)
+            .ok_or_else(|| Error::MissingKey(parent.to_string()))?;
+        let missing = || Error::MissingKey(path.clone());
+        let parent = parent.to_string();
+        let undo = match (container, value) {
+            (Value::Dict(dict), Some(value)) => {
+                let previous = dict.set(key.clone(), value);
+                Undo::Dict {
+                    parent,
+                    key,
+                    previous,
+                }
+            }
+            (Value::Dict(dict), None) => {
+                let previous = Some(dict.remove(&key).ok_or_else(missing)?);
+                Undo::Dict {
+                    parent,
+                    key,
+                    previous,
+                }
+            }
+            (Value::List(list), Some(value)) => {
+                let index = index(&key).filter(|index| *index <= list.len());
+                match index.ok_or_else(missing)? {
+                    index if index == list.len() => {
+                        list.push(value);
+                        Undo::Pop { parent }
+                    }
+                    index => Undo::Replace {
+                        parent,
+                        index,
+                        previous: std::mem::replace(&mut list[index], value),
+                    },
+                }
+            }
+            (Value::List(list), None) => {
+                let index = index(&key).filter(|index| *index < list.len());
+                let index = index.ok_or_else(missing)?;
+                Undo::Insert {
+                    parent,
+                    index,
+                    value: list.remove(index),
+                }
+            }
+            _ => {
+                return Err(Error::UnexpectedType {
+                    path: parent,
+                    expected: "list or dictionary",
+                })
+            }
+        };
+        Ok(undo)
+    }
+
+    fn revert(&mut self, undo: Undo) {
+        match undo {
+            Undo::Root(value) => *self = value,
+            Undo::Dict {
+                parent,
+                key,
+                previous,
+            } => {
+                if let Some(Value::Dict(dict)) = self.pointer_mut(&parent) {
+                    match previous {
+                        Some(value) => dict.set(key, value),
+                        None => dict.remove(&key),
+                    };
+                }
+            }
+            Undo::Replace {
+                parent,
+                index,
+                previous,
+            } => {
+                if let Some(Value::List(list)) = self.pointer_mut(&parent) {
+                    list[index] = previous;
+                }
+            }
+            Undo::Pop { parent } => {
+                if let Some(Value::List(list)) = self.pointer_mut(&parent) {
+                    list.pop();
+                }
+            }
+            Undo::Insert {
+                parent,
+                index,
+                value,
+            } => {
+                if let Some(Value::List(list)) = self.pointer_mut(&parent) {
+                    list.insert(index, value);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bencode;
+
+    fn set(path: &str, value: Value) -> Patch {
+        Patch::Set {
+            path: path.to_string(),
+            value,
+        }
+    }
+
+    fn remove(path: &str) -> Patch {
+        Patch::Remove {
+            path: path.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_patch() {
+        let mut torrent = bencode!({
+            "announce": "http://tracker",
+            "info": { "files": [{ "length": 1 }], "name": "spam" },
+        });
+        torrent
+            .patch([
+                set("/comment", bencode!("eggs")),
+                set("/info/files/0/length", bencode!(2)),
+                set("/info/files/1", bencode!({ "length": 3 })),
+                remove("/announce"),
+                remove("/info/files/0"),
+                set("/info/na~1me", bencode!("slash")),
+            ])
+            .unwrap();
+        assert_eq!(
+            torrent,
+            bencode!({
+                "comment": "eggs",
+                "info": { "files": [{ "length": 3 }], "na/me": "slash", "name": "spam" },
+            })
+        );
+
+        torrent.patch([set("", bencode!([]))]).unwrap();
+        assert_eq!(torrent, bencode!([]));
+    }
+
+    #[test]
+    fn test_patch_is_atomic() {
+        let original = bencode!({ "info": { "name": "spam" }, "list": [1] });
+        for (patch, error) in [
+            (remove("/missing"), "Missing value at /missing"),
+            (set("/a/b", bencode!(1)), "Missing value at /a"),
+            (set("/list/2", bencode!(1)), "Missing value at /list/2"),
+            (remove("/list/1"), "Missing value at /list/1"),
+            (
+                set("/info/name/x", bencode!(1)),
+                "Expected list or dictionary at /info/name",
+            ),
+            (remove(""), "Invalid pointer \"\""),
+            (remove("info"), "Invalid pointer \"info\""),
+        ] {
+            let mut value = original.clone();
+            let result = value.patch([
+                set("/info/name", bencode!("eggs")),
+                set("/info/new", bencode!(1)),
+                remove("/info/name"),
+                set("/info/name", bencode!("ham")),
+                set("/list/0", bencode!(2)),
+                set("/list/1", bencode!(3)),
+                remove("/list/0"),
+                set("/extra", bencode!({})),
+                set("/extra/x", bencode!(1)),
+                patch,
+            ]);
+            assert_eq!(result.unwrap_err().to_string(), error);
+            assert_eq!(value, original);
+        }
+
+        let mut value = original.clone();
+        let result = value.patch([set("", bencode!([])), remove("/info")]);
+        assert!(result.is_err());
+        assert_eq!(value, original);
+    }
+}
@@ -0,0 +1,224 @@
+use super::Value;
+
+/// One `/`-separated step of a pointer.
+enum Segment {
+    Key(Vec<u8>),
+    Wildcard,
+}
+
+/// Splits a pointer into unescaped segments: `~0` is `~`, `~1` is `/` and
+/// `%XX` is the byte with hex value `XX`. A bare `*` is a wildcard when
+/// `wildcards` is set and a literal key otherwise.
+fn parse(pointer: &str, wildcards: bool) -> Option<Vec<Segment>> {
+    if pointer.is_empty() {
+        return Some(Vec::new());
+    }
+    pointer
+        .strip_prefix('/')?
+        .split('/')
+        .map(|token| {
+            if wildcards && token == "*" {
+                return Some(Segment::Wildcard);
+            }
+            let mut key = Vec::with_capacity(token.len());
+            let mut bytes = token.bytes();
+            while let Some(byte) = bytes.next() {
+                match byte {
+                    b'~' => match bytes.next()? {
+                        b'0' => key.push(b'~'),
+                        b'1' => key.push(b'/'),
+                        _ => return None,
+                    },
+                    b'%' => {
+                        let high = (bytes.next()? as char).to_digit(16)?;
+                        let low = (bytes.next()? as char).to_digit(16)?;
+                        key.push((high << 4 | low) as u8);
+                    }
+                    byte => key.push(byte),
+                }
+            }
+            Some(Segment::Key(key))
+        })
+        .collect()
+}
+
+/// Splits a non-empty pointer into the pointer of its parent and the
+/// unescaped last key.
+pub(super) fn split_last(pointer: &str) -> Option<(&str, Vec<u8>)> {
+    let (parent, _) = pointer.rsplit_once('/')?;
+    match parse(&pointer[parent.len()..], false)?.pop()? {
+        Segment::Key(key) => Some((parent, key)),
+        Segment::Wildcard => None,
+    }
+}
+
+/// Parses a list index: ASCII digits only, without sign or leading zeros.
+pub(super) fn index(key: &[u8]) -> Option<usize> {
+    if key.is_empty() || !key.iter().all(u8::is_ascii_digit) || (key.len() > 1 && key[0] == b'0') {
+        return None;
+    }
+    key.iter().try_fold(0usize, |index, ch| {
+        index.checked_mul(10)?.checked_add(usize::from(ch - b'0'))
+    })
+}
+
+/// Escapes a raw dictionary key so it can be used as a pointer segment.
+///
+/// Printable ASCII is kept as is, except for `~` and `/` which become `~0`
+/// and `~1`; `%`, `*` and every other byte are written as `%XX`.
+pub fn escape_key(key: &[u8]) -> String {
+    let mut escaped = String::with_capacity(key.len());
+    for &byte in key {
+        match byte {
+            b'~' => escaped.push_str("~0"),
+            b'/' => escaped.push_str("~1"),
+            b'%' | b'*' => escaped.push_str(&format!("%{:02X}", byte)),
+            b' '..=b'~' => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    escaped
+}
+
+impl Value {
+    /// Looks up a value by a pointer like `/info/files/0/length`.
+    ///
+    /// Dictionary keys are escaped as described in [`escape_key`], list
+    /// elements are addressed by their decimal index. The empty pointer
+    /// refers to `self`.
+    pub fn pointer(&self, pointer: &str) -> Option<&Value> {
+        parse(pointer, false)?
+            .iter()
+            .try_fold(self, |value, segment| match segment {
+                Segment::Key(key) => value.child(key),
+                Segment::Wildcard => None,
+            })
+    }
+
+    /// Mutable version of [`Value::pointer`].
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut Value> {
+        parse(pointer, false)?
+            .iter()
+            .try_fold(self, |value, segment| match segment {
+                Segment::Key(key) => value.child_mut(key),
+                Segment::Wildcard => None,
+            })
+    }
+
+    /// Returns every value matching a pointer in which a `*` segment stands
+    /// for all elements of a list or all values of a dictionary, e.g.
+    /// `/info/files/*/length`. A literal `*` key is written as `%2A`.
+    pub fn select(&self, pointer: &str) -> Vec<&Value> {
+        let Some(segments) = parse(pointer, true) else {
+            return Vec::new();
+        };
+        let mut matches = vec![self];
+        for segment in &segments {
+            matches = match segment {
+                Segment::Key(key) => matches.into_iter().filter_map(|v| v.child(key)).collect(),
+                Segment::Wildcard => matches.into_iter().flat_map(Value::children).collect(),
+            };
+        }
+        matches
+    }
+
+    fn child(&self, key: &[u8]) -> Option<&Value> {
+        match self {
+            Value::Dict(dict) => dict.get(key),
+            Value::List(list) => list.get(index(key)?),
+            _ => None,
+        }
+    }
+
+    fn child_mut(&mut self, key: &[u8]) -> Option<&mut Value> {
+        match self {
+            Value::Dict(dict) => dict.get_mut(key),
+            Value::List(list) => list.get_mut(index(key)?),
+            _ => None,
+        }
+    }
+
+    fn children(&self) -> Box<dyn Iterator<Item = &Value> + '_> {
+        match self {
+            Value::Dict(dict) => Box::new(dict.values()),
+            Value::List(list) => Box::new(list.iter()),
+            _ => Box::new(std::iter::empty()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::from_bytes;
+
+    fn torrent() -> Value {
+        from_bytes(
+            b"d8:announce3:url4:infod5:filesld6:lengthi1e4:pathl1:aeed6:lengthi2e4:pathl1:beee4:name4:spame\
+              3:a/bi1e3:a~bi2e1:*i3e2:\xeb\x8bi4ee",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_pointer() {
+        let torrent = torrent();
+        assert_eq!(torrent.pointer(""), Some(&torrent));
+        assert_eq!(
+            torrent.pointer("/info/files/1/length"),
+            Some(&Value::Integer(2))
+        );
+        assert_eq!(
+            torrent.pointer("/info/name"),
+            Some(&Value::Bytes(b"spam".to_vec()))
+        );
+        assert_eq!(torrent.pointer("/a~1b"), Some(&Value::Integer(1)));
+        assert_eq!(torrent.pointer("/a~0b"), Some(&Value::Integer(2)));
+        assert_eq!(torrent.pointer("/*"), Some(&Value::Integer(3)));
+        assert_eq!(torrent.pointer("/%eb%8B"), Some(&Value::Integer(4)));
+        assert_eq!(torrent.pointer("/info/files/01/length"), None);
+        assert_eq!(torrent.pointer("/info/files/+1/length"), None);
+        assert_eq!(torrent.pointer("/info/files/-0/length"), None);
+        assert_eq!(torrent.pointer("/info/files/ 1/length"), None);
+        assert_eq!(torrent.pointer("/info/files/2"), None);
+        assert_eq!(torrent.pointer("info"), None);
+        assert_eq!(torrent.pointer("/%e"), None);
+        assert_eq!(torrent.pointer("/a~2b"), None);
+    }
+
+    #[test]
+    fn test_pointer_mut() {
+        let mut torrent = torrent();
+        *torrent.pointer_mut("/info/files/0/length").unwrap() = Value::Integer(10);
+        assert_eq!(
+            torrent.pointer("/info/files/0/length"),
+            Some(&Value::Integer(10))
+        );
+    }
+
+    #[test]
+    fn test_select() {
+        let torrent = torrent();
+        assert_eq!(
+            torrent.select("/info/files/*/length"),
+            vec![&Value::Integer(1), &Value::Integer(2)]
+        );
+        assert_eq!(
+            torrent.select("/info/files/*/path/*"),
+            vec![&Value::Bytes(b"a".to_vec()), &Value::Bytes(b"b".to_vec())]
+        );
+        assert_eq!(torrent.select("/%2A"), vec![&Value::Integer(3)]);
+        assert_eq!(torrent.select("/*").len(), 6);
+        assert!(torrent.select("/info/*/length").is_empty());
+    }
+
+    #[test]
+    fn test_escape_key() {
+        let torrent = torrent();
+        for key in [&b"a/b"[..], b"a~b", b"*", b"\xeb\x8b"] {
+            assert!(torrent.pointer(&format!("/{}", escape_key(key))).is_some());
+            assert_eq!(torrent.select(&format!("/{}", escape_key(key))).len(), 1);
+        }
+        assert_eq!(escape_key(b"a b%\x00"), "a b%25%00");
+    }
+}
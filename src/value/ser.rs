@@ -0,0 +1,443 @@
+use std::collections::BTreeMap;
+
+use serde::ser::{self, Serialize, SerializeMap, Serializer};
+use serde_bytes::Bytes;
+
+use super::{Dict, Value};
+use crate::de::from_bytes;
+use crate::error::Error;
+use crate::raw;
+
+impl Serialize for Value {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Value::Integer(integer) => serializer.serialize_i64(*integer),
+            Value::Bytes(bytes) => serializer.serialize_bytes(bytes),
+            Value::List(list) => serializer.collect_seq(list),
+            Value::Dict(dict) => {
+                let mut map = serializer.serialize_map(Some(dict.len()))?;
+                for (key, value) in dict {
+                    map.serialize_entry(Bytes::new(key), value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+/// Serializer building a [`Value`] instead of bytes.
+///
+/// `None` and `()` produce no value at all, exactly like the byte-level
+/// serializer writes nothing for them: they are dropped from lists and their
+/// keys are dropped from dictionaries.
+pub(crate) struct ValueSerializer;
+
+fn integer<T>(v: T) -> Result<Option<Value>, Error>
+where
+    i64: TryFrom<T>,
+{
+    i64::try_from(v)
+        .map(|v| Some(Value::Integer(v)))
+        .map_err(|_| Error::LargeNumber)
+}
+
+fn single_entry(key: &str, value: Value) -> Option<Value> {
+    Some(Value::Dict(Dict::new().insert(key, value)))
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Option<Value>;
+
+    type Error = Error;
+
+    type SerializeSeq = SerializeList;
+
+    type SerializeTuple = SerializeList;
+
+    type SerializeTupleStruct = SerializeList;
+
+    type SerializeTupleVariant = SerializeList;
+
+    type SerializeMap = SerializeDict;
+
+    type SerializeStruct = SerializeDict;
+
+    type SerializeStructVariant = SerializeDict;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        integer(v as u8)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        integer(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        integer(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        integer(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        integer(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        integer(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        integer(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        integer(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        integer(v)
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<Self::Ok, Self::Error> {
+        Err(Error::InvalidValue("Cannot serialize f32"))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<Self::Ok, Self::Error> {
+        Err(Error::InvalidValue("Cannot serialize f64"))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        let mut buf = [0u8; 4];
+        self.serialize_bytes(v.encode_utf8(&mut buf).as_bytes())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        self.serialize_bytes(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Value::Bytes(v.to_vec())))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        match value.serialize(self)? {
+            Some(Value::Bytes(raw)) if name == raw::TOKEN => from_bytes(&raw).map(Some),
+            value => Ok(value),
+        }
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(match value.serialize(self)? {
+            Some(value) => single_entry(variant, value),
+            None => Some(Value::Dict(Dict::new())),
+        })
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(SerializeList {
+            variant: None,
+            list: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Ok(SerializeList {
+            variant: Some(variant),
+            list: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(SerializeDict {
+            variant: None,
+            dict: BTreeMap::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Ok(SerializeDict {
+            variant: Some(variant),
+            dict: BTreeMap::new(),
+            key: None,
+        })
+    }
+}
+
+pub(crate) struct SerializeList {
+    variant: Option<&'static str>,
+    list: Vec<Value>,
+}
+
+impl SerializeList {
+    fn push<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if let Some(value) = value.serialize(ValueSerializer)? {
+            self.list.push(value);
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Option<Value>, Error> {
+        let list = Value::List(self.list);
+        Ok(match self.variant {
+            Some(variant) => single_entry(variant, list),
+            None => Some(list),
+        })
+    }
+}
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = Option<Value>;
+
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = Option<Value>;
+
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = Option<Value>;
+
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeList {
+    type Ok = Option<Value>;
+
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+pub(crate) struct SerializeDict {
+    variant: Option<&'static str>,
+    dict: BTreeMap<Vec<u8>, Value>,
+    key: Option<Vec<u8>>,
+}
+
+impl SerializeDict {
+    fn insert<T>(&mut self, key: Vec<u8>, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if let Some(value) = value.serialize(ValueSerializer)? {
+            self.dict.insert(key, value);
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Option<Value>, Error> {
+        let dict = Value::Dict(self.dict.into());
+        Ok(match self.variant {
+            Some(variant) => single_entry(variant, dict),
+            None => Some(dict),
+        })
+    }
+}
+
+impl ser::SerializeMap for SerializeDict {
+    type Ok = Option<Value>;
+
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        match key.serialize(ValueSerializer)? {
+            Some(Value::Bytes(key)) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => Err(Error::InvalidValue("Dictionary keys must be byte strings")),
+        }
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let key = self
+            .key
+            .take()
+            .ok_or(Error::InvalidValue("Dictionary value without a key"))?;
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for SerializeDict {
+    type Ok = Option<Value>;
+
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.insert(key.as_bytes().to_vec(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeDict {
+    type Ok = Option<Value>;
+
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.insert(key.as_bytes().to_vec(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+/// Converts `value` into a [`Value`] without going through bytes.
+pub fn to_value<T>(value: &T) -> Result<Value, Error>
+where
+    T: ?Sized + Serialize,
+{
+    value
+        .serialize(ValueSerializer)?
+        .ok_or(Error::InvalidValue("Value serializes to nothing"))
+}
@@ -0,0 +1,123 @@
+//! Message types and fixtures shared by the integration tests and benchmarks.
+#![allow(dead_code)]
+
+use std::net::SocketAddr;
+
+use bencode::utils::compact::CompactNode;
+use bencode::utils::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct Error(u64, String);
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct Ping {
+    #[serde(with = "binary_string")]
+    pub id: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct FindNode {
+    #[serde(with = "binary_string")]
+    pub id: Vec<u8>,
+    #[serde(with = "binary_string")]
+    pub target: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct FindNodeResponse {
+    #[serde(with = "binary_string")]
+    pub id: Vec<u8>,
+    #[serde(with = "compact::nodes")]
+    pub nodes: Vec<CompactNode>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct GetPeers {
+    #[serde(with = "binary_string")]
+    pub id: Vec<u8>,
+    #[serde(with = "binary_string")]
+    pub info_hash: Vec<u8>,
+}
+
+/// Carries either `values` or `nodes`, but always a `token`, which is what
+/// tells it apart from a [`FindNodeResponse`].
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct GetPeersResponse {
+    #[serde(with = "binary_string")]
+    pub id: Vec<u8>,
+    #[serde(
+        default,
+        skip_serializing_if = "Vec::is_empty",
+        with = "compact::nodes"
+    )]
+    pub nodes: Vec<CompactNode>,
+    #[serde(with = "binary_string")]
+    pub token: Vec<u8>,
+    #[serde(
+        default,
+        skip_serializing_if = "Vec::is_empty",
+        with = "compact::peers"
+    )]
+    pub values: Vec<SocketAddr>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct AnnouncePeer {
+    #[serde(with = "binary_string")]
+    pub id: Vec<u8>,
+    #[serde(with = "binary_string")]
+    pub info_hash: Vec<u8>,
+    pub port: u16,
+    pub implied_port: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "q", content = "a")]
+pub enum Query {
+    Ping(Ping),
+    FindNode(FindNode),
+    GetPeers(GetPeers),
+    AnnouncePeer(AnnouncePeer),
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum Response {
+    // Untagged variants are tried in order, so the ones with more required
+    // fields come first.
+    GetPeers(GetPeersResponse),
+    FindNode(FindNodeResponse),
+    AnnouncePeer(Ping),
+    Ping(Ping),
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct ResponseWrapper {
+    #[serde(rename = "r")]
+    pub inner: Response,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+#[serde(tag = "y")]
+pub enum MessageData {
+    #[serde(rename = "q")]
+    Query(Query),
+    #[serde(rename = "r")]
+    Response(ResponseWrapper),
+    #[serde(rename = "e")]
+    Error(Error),
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct Message {
+    #[serde(with = "binary_string")]
+    pub t: Vec<u8>,
+    #[serde(flatten)]
+    pub inner: MessageData,
+}
+
+pub const GET_PEERS_QUERY: &str = "64313a6164323a6273693165323a696432303a5fbb5ddff6ddf9074480fa82f538a8d80f33d405393a696e666f5f6861736832303a5fbb5ddff6ddf9074480fa8283e35f1fc55353ab65313a71393a6765745f7065657273313a74323aeb8b313a76343a4c54012f313a79313a7165";
+
+pub const GET_PEERS_RESPONSE: &str = "64313a7264323a696432303a6b5611b3bfb7b8c8372c069a1e22e540609bda5a353a6e6f6465733230383a22f6d37eb95dc510c5330ed90f850d9d95d3992cb125b5c0acae22f6d37eb95dc510c5330ed90f850d9d95d3992cb125b5c0acae22f6d37eb95dc510c5330ed90f850d9d95d3992cb125b5c0acae22f6d37eb95dc510c5330ed90f850d9d95d3992cb125b5c0acae22f6d37eb95dc510c5330ed90f850d9d95d3992cb125b5c0acae22f6d37eb95dc510c5330ed90f850d9d95d3992cb125b5c0acae22f6d37eb95dc510c5330ed90f850d9d95d3992cb125b5c0acae22f6d37eb95dc510c5330ed90f850d9d95d3992cb125b5c0acae65313a74323a42af313a76343a4a420000313a79313a7265";
@@ -1,114 +1,13 @@
 /// All tests are done with real data sent by QbitTorrent, captured via Wireshark
-use bencode::utils::*;
-use serde::Deserialize;
-use serde::{self, Serialize};
-use std::net::SocketAddr;
+mod common;
 
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
-pub struct Node {
-    pub id: String,
-    pub addr: SocketAddr,
-}
-
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
-pub struct Error(u64, String);
-
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
-pub struct Ping {
-    #[serde(with = "binary_string")]
-    id: Vec<u8>,
-}
-
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
-pub struct FindNode {
-    #[serde(with = "binary_string")]
-    id: Vec<u8>,
-    #[serde(with = "binary_string")]
-    target: Vec<u8>,
-}
-
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
-pub struct FindNodeResponse {
-    #[serde(with = "binary_string")]
-    id: Vec<u8>,
-    nodes: Vec<Node>,
-}
-
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
-pub struct GetPeers {
-    #[serde(with = "binary_string")]
-    id: Vec<u8>,
-    #[serde(with = "binary_string")]
-    info_hash: Vec<u8>,
-}
-
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
-pub struct GetPeersResponse {
-    #[serde(with = "binary_string")]
-    id: Vec<u8>,
-    #[serde(with = "binary_string")]
-    nodes: Vec<u8>,
-}
-
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
-pub struct AnnouncePeer {
-    #[serde(with = "binary_string")]
-    id: Vec<u8>,
-    #[serde(with = "binary_string")]
-    info_hash: Vec<u8>,
-    port: u16,
-    implied_port: bool,
-}
-
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
-#[serde(rename_all = "snake_case")]
-#[serde(tag = "q", content = "a")]
-pub enum Query {
-    Ping(Ping),
-    FindNode(FindNode),
-    GetPeers(GetPeers),
-    AnnouncePeer(AnnouncePeer),
-}
-
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
-#[serde(untagged)]
-pub enum Response {
-    GetPeers(GetPeersResponse),
-    AnnouncePeer(Ping),
-    Ping(Ping),
-    //FindNode(FindNodeResponse),
-}
-
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
-pub struct ResponseWrapper {
-    #[serde(rename = "r")]
-    inner: Response,
-}
-
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
-#[serde(tag = "y")]
-pub enum MessageData {
-    #[serde(rename = "q")]
-    Query(Query),
-    #[serde(rename = "r")]
-    Response(ResponseWrapper),
-    #[serde(rename = "e")]
-    Error(Error),
-}
-
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
-pub struct Message {
-    #[serde(with = "binary_string")]
-    pub t: Vec<u8>,
-    #[serde(flatten)]
-    pub inner: MessageData,
-}
+use bencode::utils::compact::CompactNode;
+use common::*;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
 
 #[test]
 fn test_deserialization() {
-    let message = "64313a6164323a6273693165323a696432303a5fbb5ddff6ddf9074480fa82f538a8d80f33d405393a696e666f5f6861736832303a5fbb5ddff6ddf9074480fa8283e35f1fc55353ab65313a71393a6765745f7065657273313a74323aeb8b313a76343a4c54012f313a79313a7165";
-
-    let message = hex::decode(message).unwrap();
+    let message = hex::decode(GET_PEERS_QUERY).unwrap();
     let deserialized: Message = bencode::from_bytes(&message).unwrap();
 
     let right = Message {
@@ -129,33 +28,28 @@ fn test_deserialization() {
 
     println!("{:?}", deserialized);
 
-    let message = "64313a7264323a696432303a6b5611b3bfb7b8c8372c069a1e22e540609bda5a353a6e6f6465733230383a22f6d37eb95dc510c5330ed90f850d9d95d3992cb125b5c0acae22f6d37eb95dc510c5330ed90f850d9d95d3992cb125b5c0acae22f6d37eb95dc510c5330ed90f850d9d95d3992cb125b5c0acae22f6d37eb95dc510c5330ed90f850d9d95d3992cb125b5c0acae22f6d37eb95dc510c5330ed90f850d9d95d3992cb125b5c0acae22f6d37eb95dc510c5330ed90f850d9d95d3992cb125b5c0acae22f6d37eb95dc510c5330ed90f850d9d95d3992cb125b5c0acae22f6d37eb95dc510c5330ed90f850d9d95d3992cb125b5c0acae65313a74323a42af313a76343a4a420000313a79313a7265";
-
-    let message = hex::decode(message).unwrap();
+    // The node answered get_peers without a token, which on the wire is
+    // exactly a find_node response.
+    let message = hex::decode(GET_PEERS_RESPONSE).unwrap();
     let deserialized: Message = bencode::from_bytes(&message).unwrap();
 
     let right = Message {
         t: vec![66, 175],
         inner: MessageData::Response(ResponseWrapper {
-            inner: Response::GetPeers(GetPeersResponse {
+            inner: Response::FindNode(FindNodeResponse {
                 id: vec![
                     107, 86, 17, 179, 191, 183, 184, 200, 55, 44, 6, 154, 30, 34, 229, 64, 96, 155,
                     218, 90,
                 ],
                 nodes: vec![
-                    34, 246, 211, 126, 185, 93, 197, 16, 197, 51, 14, 217, 15, 133, 13, 157, 149,
-                    211, 153, 44, 177, 37, 181, 192, 172, 174, 34, 246, 211, 126, 185, 93, 197, 16,
-                    197, 51, 14, 217, 15, 133, 13, 157, 149, 211, 153, 44, 177, 37, 181, 192, 172,
-                    174, 34, 246, 211, 126, 185, 93, 197, 16, 197, 51, 14, 217, 15, 133, 13, 157,
-                    149, 211, 153, 44, 177, 37, 181, 192, 172, 174, 34, 246, 211, 126, 185, 93,
-                    197, 16, 197, 51, 14, 217, 15, 133, 13, 157, 149, 211, 153, 44, 177, 37, 181,
-                    192, 172, 174, 34, 246, 211, 126, 185, 93, 197, 16, 197, 51, 14, 217, 15, 133,
-                    13, 157, 149, 211, 153, 44, 177, 37, 181, 192, 172, 174, 34, 246, 211, 126,
-                    185, 93, 197, 16, 197, 51, 14, 217, 15, 133, 13, 157, 149, 211, 153, 44, 177,
-                    37, 181, 192, 172, 174, 34, 246, 211, 126, 185, 93, 197, 16, 197, 51, 14, 217,
-                    15, 133, 13, 157, 149, 211, 153, 44, 177, 37, 181, 192, 172, 174, 34, 246, 211,
-                    126, 185, 93, 197, 16, 197, 51, 14, 217, 15, 133, 13, 157, 149, 211, 153, 44,
-                    177, 37, 181, 192, 172, 174,
+                    CompactNode {
+                        id: [
+                            34, 246, 211, 126, 185, 93, 197, 16, 197, 51, 14, 217, 15, 133, 13,
+                            157, 149, 211, 153, 44,
+                        ],
+                        addr: SocketAddrV4::new(Ipv4Addr::new(177, 37, 181, 192), 44206),
+                    };
+                    8
                 ],
             }),
         }),
@@ -165,3 +59,111 @@ fn test_deserialization() {
 
     println!("{:?}", deserialized);
 }
+
+#[test]
+fn test_response_variants() {
+    let node = CompactNode {
+        id: [1; 20],
+        addr: SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 6881),
+    };
+    for response in [
+        Response::GetPeers(GetPeersResponse {
+            id: vec![2; 20],
+            nodes: vec![],
+            token: b"aoeusnth".to_vec(),
+            values: vec![SocketAddr::from(([10, 0, 0, 2], 6882))],
+        }),
+        Response::GetPeers(GetPeersResponse {
+            id: vec![2; 20],
+            nodes: vec![node],
+            token: b"aoeusnth".to_vec(),
+            values: vec![],
+        }),
+        Response::FindNode(FindNodeResponse {
+            id: vec![2; 20],
+            nodes: vec![node],
+        }),
+        Response::AnnouncePeer(Ping { id: vec![2; 20] }),
+    ] {
+        let message = Message {
+            t: b"aa".to_vec(),
+            inner: MessageData::Response(ResponseWrapper { inner: response }),
+        };
+        let encoded = bencode::to_bytes(&message).unwrap();
+        assert_eq!(bencode::from_bytes::<Message>(&encoded).unwrap(), message);
+    }
+
+    let mut find_node = b"d1:rd2:id20:".to_vec();
+    find_node.extend([2; 20]);
+    find_node.extend(b"5:nodes26:");
+    find_node.extend([1; 20]);
+    find_node.extend([10, 0, 0, 1, 0x1a, 0xe1]);
+    find_node.extend(b"e1:t2:aa1:y1:re");
+    let message: Message = bencode::from_bytes(&find_node).unwrap();
+    assert!(matches!(
+        message.inner,
+        MessageData::Response(ResponseWrapper {
+            inner: Response::FindNode(_)
+        })
+    ));
+}
+
+#[test]
+fn test_corrupted_messages_do_not_panic() {
+    for message in [GET_PEERS_QUERY, GET_PEERS_RESPONSE] {
+        let message = hex::decode(message).unwrap();
+        for len in 0..message.len() {
+            let _ = bencode::from_bytes::<Message>(&message[..len]);
+            let _ = bencode::from_bytes::<serde::de::IgnoredAny>(&message[..len]);
+        }
+        for index in 0..message.len() {
+            for byte in [0, b'0', b'9', b':', b'-', b'd', b'e', b'i', b'l', 0xff] {
+                let mut corrupted = message.clone();
+                corrupted[index] = byte;
+                let _ = bencode::from_bytes::<Message>(&corrupted);
+                let _ = bencode::from_bytes::<serde::de::IgnoredAny>(&corrupted);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_value_round_trip() {
+    for message in [GET_PEERS_QUERY, GET_PEERS_RESPONSE] {
+        let message = hex::decode(message).unwrap();
+        let value: bencode::Value = bencode::from_bytes(&message).unwrap();
+        assert_eq!(bencode::to_bytes(&value).unwrap(), message);
+    }
+}
+
+#[test]
+fn test_value_to_typed() {
+    for message in [GET_PEERS_QUERY, GET_PEERS_RESPONSE] {
+        let message = hex::decode(message).unwrap();
+        let value: bencode::Value = bencode::from_bytes(&message).unwrap();
+        let typed: Message = bencode::from_bytes(&message).unwrap();
+        assert_eq!(
+            bencode::from_value::<Message>(value.clone()).unwrap(),
+            typed
+        );
+        assert_eq!(
+            bencode::to_value(&typed).unwrap(),
+            bencode::from_bytes::<bencode::Value>(&bencode::to_bytes(&typed).unwrap()).unwrap()
+        );
+    }
+}
+
+#[test]
+fn test_bencode_macro_fixture() {
+    let id = b"\x5f\xbb\x5d\xdf\xf6\xdd\xf9\x07\x44\x80\xfa\x82\xf5\x38\xa8\xd8\x0f\x33\xd4\x05";
+    let info_hash =
+        b"\x5f\xbb\x5d\xdf\xf6\xdd\xf9\x07\x44\x80\xfa\x82\x83\xe3\x5f\x1f\xc5\x53\x53\xab";
+    let message = bencode::bencode_bytes!({
+        "a": { "bs": 1, "id": id, "info_hash": info_hash },
+        "q": "get_peers",
+        "t": b"\xeb\x8b",
+        "v": b"LT\x01\x2f",
+        "y": "q",
+    });
+    assert_eq!(message, hex::decode(GET_PEERS_QUERY).unwrap());
+}